@@ -0,0 +1,134 @@
+//! zome calls by role, for a single installed app.
+//!
+//! an `AppClient` looks cells up in its app's `AppInfo`, which it fetches once and caches. a role
+//! it doesn't know about triggers a refresh before giving up, so cells added since (e.g. clones)
+//! are picked up.
+
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use wasm_bindgen::prelude::*;
+
+use crate::{AppInfo, AppWebsocket, AppWsCmd, AppWsCmdResponse, CallError, CellId};
+
+#[derive(Clone, Debug)]
+pub enum AppClientError {
+    /// the app has no cell with this role, even after refreshing its `AppInfo`.
+    UnknownRole(String),
+    Call(CallError),
+}
+
+impl From<CallError> for AppClientError {
+    fn from(err: CallError) -> Self {
+        AppClientError::Call(err)
+    }
+}
+
+impl fmt::Display for AppClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppClientError::UnknownRole(role_id) => write!(f, "no cell with role {}", role_id),
+            AppClientError::Call(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for AppClientError {}
+
+/// an `AppWebsocket` bound to `installed_app_id`. clones share the cached `AppInfo`.
+#[derive(Clone, Debug)]
+pub struct AppClient {
+    ws: AppWebsocket,
+    installed_app_id: String,
+    app_info: Rc<RefCell<Option<AppInfo>>>,
+}
+
+impl AppClient {
+    /// `AppInfo` isn't fetched until it's first needed.
+    pub fn new(ws: AppWebsocket, installed_app_id: impl Into<String>) -> Self {
+        AppClient {
+            ws,
+            installed_app_id: installed_app_id.into(),
+            app_info: Rc::default(),
+        }
+    }
+
+    pub fn ws(&self) -> &AppWebsocket {
+        &self.ws
+    }
+
+    pub fn installed_app_id(&self) -> &str {
+        &self.installed_app_id
+    }
+
+    /// the cached `AppInfo`, fetched if there isn't one yet.
+    pub async fn app_info(&self) -> Result<AppInfo, CallError> {
+        let cached = self.app_info.borrow().clone();
+        match cached {
+            Some(app_info) => Ok(app_info),
+            None => self.refresh().await,
+        }
+    }
+
+    /// fetches & caches the app's current `AppInfo`.
+    pub async fn refresh(&self) -> Result<AppInfo, CallError> {
+        let resp = self
+            .ws
+            .call(AppWsCmd::AppInfo {
+                installed_app_id: self.installed_app_id.clone(),
+            })
+            .await?;
+        let app_info = match resp {
+            AppWsCmdResponse::AppInfo(app_info) => app_info,
+            other => panic!("AppClient::refresh: impossible: received {:?}", other),
+        };
+        *self.app_info.borrow_mut() = Some(app_info.clone());
+        Ok(app_info)
+    }
+
+    /// drops the cached `AppInfo`, e.g. after changing the app's cells.
+    pub(crate) fn invalidate(&self) {
+        self.app_info.borrow_mut().take();
+    }
+
+    /// the cell playing `role_id` (or the clone with that clone id), refreshing the cached
+    /// `AppInfo` if it has no such role.
+    pub async fn cell_id(&self, role_id: &str) -> Result<CellId, AppClientError> {
+        if let Some(cell_id) = self.app_info().await?.cell_id(role_id) {
+            return Ok(cell_id);
+        }
+        self.refresh()
+            .await?
+            .cell_id(role_id)
+            .ok_or_else(|| AppClientError::UnknownRole(role_id.into()))
+    }
+
+    /// calls `zome_name`/`fn_name` in the cell playing `role_id`, as the cell's own agent.
+    pub async fn call_zome_by_role(
+        &self,
+        role_id: &str,
+        zome_name: impl Into<String>,
+        fn_name: impl Into<String>,
+        payload: JsValue,
+    ) -> Result<JsValue, AppClientError> {
+        let cell_id = self.cell_id(role_id).await?;
+        let provenance = cell_id.1.clone();
+        let resp = self
+            .ws
+            .call(AppWsCmd::CallZome {
+                cell_id,
+                zome_name: zome_name.into(),
+                fn_name: fn_name.into(),
+                payload,
+                provenance,
+                cap: None,
+            })
+            .await?;
+        match resp {
+            AppWsCmdResponse::CallZome(val) => Ok(val),
+            other => panic!(
+                "AppClient::call_zome_by_role: impossible: received {:?}",
+                other
+            ),
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! app interfaces: the conductor ports app websockets connect to.
+//!
+//! `AdminWebsocket::app_ws` reuses an interface the conductor already has, or attaches one on a
+//! port of the conductor's choosing, and connects to it on the admin websocket's host.
+
+use std::fmt;
+
+use js_sys::{Array, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    connect_app_ws, AdminWebsocket, AdminWsCmd, AdminWsCmdResponse, AppWebsocket, CallError,
+    DeserializeFromJsObj,
+};
+
+#[derive(Clone, Debug)]
+pub enum AppInterfaceError {
+    Call(CallError),
+    /// connecting to the interface failed.
+    Connect(String),
+}
+
+impl From<CallError> for AppInterfaceError {
+    fn from(err: CallError) -> Self {
+        AppInterfaceError::Call(err)
+    }
+}
+
+impl fmt::Display for AppInterfaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AppInterfaceError::Call(err) => write!(f, "{}", err),
+            AppInterfaceError::Connect(err) => {
+                write!(f, "connecting to the app interface failed: {}", err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AppInterfaceError {}
+
+impl AdminWebsocket {
+    /// the ports of the conductor's app interfaces.
+    pub async fn app_interfaces(&self) -> Result<Vec<u16>, CallError> {
+        match self.call(AdminWsCmd::ListAppInterfaces).await? {
+            AdminWsCmdResponse::ListAppInterfaces(ports) => Ok(ports),
+            other => panic!(
+                "AdminWebsocket::app_interfaces: impossible: received {:?}",
+                other
+            ),
+        }
+    }
+
+    /// attaches an app interface on `port`, or on a free port the conductor picks if it's 0.
+    /// returns the port attached.
+    pub async fn attach_app_interface(&self, port: u16) -> Result<u16, CallError> {
+        match self.call(AdminWsCmd::AttachAppInterface { port }).await? {
+            AdminWsCmdResponse::AttachAppInterface(port) => Ok(port),
+            other => panic!(
+                "AdminWebsocket::attach_app_interface: impossible: received {:?}",
+                other
+            ),
+        }
+    }
+
+    /// connects to the conductor's first app interface, attaching one if it has none. the
+    /// connection shares this websocket's `call_timeout`.
+    pub async fn app_ws(&self) -> Result<AppWebsocket, AppInterfaceError> {
+        let port = match self.app_interfaces().await?.first() {
+            Some(port) => *port,
+            None => self.attach_app_interface(0).await?,
+        };
+        connect_app_ws(self.app_interface_url(port), self.call_timeout)
+            .await
+            .map_err(AppInterfaceError::Connect)
+    }
+
+    /// the url of the app interface on `port`: this websocket's, with the port swapped, or
+    /// `ws://localhost:{port}` if it doesn't know its url.
+    pub fn app_interface_url(&self, port: u16) -> String {
+        let url = match &self.url {
+            Some(url) => url,
+            None => return format!("ws://localhost:{}", port),
+        };
+        let (scheme, rest) = url.split_once("://").unwrap_or(("ws", url));
+        let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+        // a port follows the last `:`, unless that's inside an IPv6 address's brackets.
+        let host = match authority.rsplit_once(':') {
+            Some((host, port)) if !port.contains(']') => host,
+            _ => authority,
+        };
+        format!("{}://{}:{}", scheme, host, port)
+    }
+}
+
+/// `{ port }`, as conductors respond to `AttachAppInterface`.
+pub(crate) fn attached_port(val: JsValue) -> u16 {
+    u16::deserialize_from_js_obj(
+        Reflect::get(&val, &JsValue::from_str("port")).expect("object field get to succeed"),
+    )
+}
+
+/// `ListAppInterfaces` responses: ports, or from newer conductors, `{ port, .. }` objects.
+pub(crate) fn listed_ports(val: JsValue) -> Vec<u16> {
+    Array::from(&val)
+        .iter()
+        .map(|interface| match interface.as_f64() {
+            Some(_) => u16::deserialize_from_js_obj(interface),
+            None => attached_port(interface),
+        })
+        .collect()
+}
@@ -0,0 +1,82 @@
+//! single-threaded fan-out of values to any number of independent `Stream` subscribers.
+
+use std::{
+    cell::{Cell, RefCell},
+    pin::Pin,
+    rc::{Rc, Weak},
+    task::{Context, Poll},
+};
+
+use futures::{
+    channel::mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+    Stream,
+};
+
+pub(crate) struct Subscribers<T> {
+    next_id: Cell<u64>,
+    senders: RefCell<Vec<(u64, UnboundedSender<T>)>>,
+}
+
+impl<T> Default for Subscribers<T> {
+    fn default() -> Self {
+        Subscribers {
+            next_id: Cell::new(0),
+            senders: RefCell::new(Vec::new()),
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Subscribers<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Subscribers")
+            .field("count", &self.senders.borrow().len())
+            .finish()
+    }
+}
+
+impl<T: Clone> Subscribers<T> {
+    pub(crate) fn subscribe(self: &Rc<Self>) -> Subscription<T> {
+        let id = self.next_id.get();
+        self.next_id.set(id + 1);
+        let (sender, receiver) = unbounded();
+        self.senders.borrow_mut().push((id, sender));
+        Subscription {
+            id,
+            receiver,
+            subscribers: Rc::downgrade(self),
+        }
+    }
+
+    pub(crate) fn publish(&self, val: T) {
+        self.senders
+            .borrow_mut()
+            .retain(|(_, sender)| sender.unbounded_send(val.clone()).is_ok());
+    }
+}
+
+/// a stream of every value published after subscribing. dropping it unsubscribes.
+#[derive(Debug)]
+pub struct Subscription<T> {
+    id: u64,
+    receiver: UnboundedReceiver<T>,
+    subscribers: Weak<Subscribers<T>>,
+}
+
+impl<T> Stream for Subscription<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        Pin::new(&mut self.receiver).poll_next(cx)
+    }
+}
+
+impl<T> Drop for Subscription<T> {
+    fn drop(&mut self) {
+        if let Some(subscribers) = self.subscribers.upgrade() {
+            subscribers
+                .senders
+                .borrow_mut()
+                .retain(|(id, _)| *id != self.id);
+        }
+    }
+}
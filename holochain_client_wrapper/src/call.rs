@@ -0,0 +1,157 @@
+//! per-call deadlines & cancellation.
+//!
+//! every `call` on a websocket races the JS client's promise against its deadline (the
+//! connection's `call_timeout` unless `CallOptions` overrides it) and, optionally, a
+//! `Cancellation`. whichever finishes first decides the result, so a stuck promise can no longer
+//! hang a caller forever.
+
+use std::{fmt, future::Future};
+
+use futures::{
+    channel::oneshot,
+    future::{self, Either},
+};
+use js_sys::{Function, Promise};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_name = setTimeout)]
+    fn set_timeout(handler: &Function, timeout: i32) -> JsValue;
+
+    #[wasm_bindgen(js_name = clearTimeout)]
+    fn clear_timeout(handle: &JsValue);
+}
+
+/// clears its timer when dropped, so abandoned sleeps (e.g. the deadline of a call which already
+/// finished) don't keep the event loop busy.
+struct Timer(JsValue);
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        clear_timeout(&self.0);
+    }
+}
+
+/// resolves after `ms` milliseconds. `setTimeout` takes an i32, so longer waits are clamped to
+/// `i32::MAX` ms (~24.8 days) rather than wrapping to a negative, i.e. immediate, timeout.
+pub(crate) async fn sleep(ms: u32) {
+    let ms = ms.min(i32::MAX as u32) as i32;
+    let mut timer = None;
+    let promise = Promise::new(&mut |resolve, _reject| {
+        timer = Some(Timer(set_timeout(&resolve, ms)));
+    });
+    let _timer = timer;
+    let _ = JsFuture::from(promise).await;
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// CallError
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug)]
+pub enum CallError {
+    /// the JS client rejected the call, or threw while it was being made.
+    Js(JsValue),
+    /// the deadline passed before the conductor responded.
+    Timeout,
+    /// the call was aborted through its `CancelHandle`.
+    Cancelled,
+    /// the connection is closed and won't be reopened.
+    Closed,
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::Js(val) => write!(f, "{:?}", val),
+            CallError::Timeout => write!(f, "call timed out"),
+            CallError::Cancelled => write!(f, "call was cancelled"),
+            CallError::Closed => write!(f, "connection is closed"),
+        }
+    }
+}
+
+impl std::error::Error for CallError {}
+
+impl From<JsValue> for CallError {
+    fn from(val: JsValue) -> Self {
+        CallError::Js(val)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// CallOptions
+////////////////////////////////////////////////////////////////////////////////
+
+/// aborts the associated call when `cancel`led or dropped.
+#[derive(Debug)]
+pub struct CancelHandle(oneshot::Sender<()>);
+
+/// the receiving half of a `CancelHandle`, handed to `CallOptions::cancellation`.
+#[derive(Debug)]
+pub struct Cancellation(oneshot::Receiver<()>);
+
+impl CancelHandle {
+    pub fn pair() -> (CancelHandle, Cancellation) {
+        let (sender, receiver) = oneshot::channel();
+        (CancelHandle(sender), Cancellation(receiver))
+    }
+
+    pub fn cancel(self) {
+        let _ = self.0.send(());
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct CallOptions {
+    /// deadline in milliseconds. `None` falls back to the connection's `call_timeout`.
+    pub timeout: Option<u32>,
+    pub cancellation: Option<Cancellation>,
+}
+
+impl CallOptions {
+    pub fn timeout(mut self, ms: u32) -> Self {
+        self.timeout = Some(ms);
+        self
+    }
+
+    pub fn cancellation(mut self, cancellation: Cancellation) -> Self {
+        self.cancellation = Some(cancellation);
+        self
+    }
+}
+
+/// drives `fut` to completion unless `timeout` (ms) passes or `cancellation` fires first.
+pub(crate) async fn with_deadline<T>(
+    fut: impl Future<Output = Result<T, JsValue>>,
+    timeout: Option<u32>,
+    cancellation: Option<Cancellation>,
+) -> Result<T, CallError> {
+    let deadline = async move {
+        match timeout {
+            Some(ms) => sleep(ms).await,
+            None => future::pending().await,
+        }
+    };
+    let cancelled = async move {
+        match cancellation {
+            // a dropped `CancelHandle` cancels too.
+            Some(Cancellation(receiver)) => {
+                let _ = receiver.await;
+            }
+            None => future::pending().await,
+        }
+    };
+    let interrupt = async move {
+        match future::select(Box::pin(deadline), Box::pin(cancelled)).await {
+            Either::Left(_) => CallError::Timeout,
+            Either::Right(_) => CallError::Cancelled,
+        }
+    };
+    match future::select(Box::pin(fut), Box::pin(interrupt)).await {
+        Either::Left((res, _)) => res.map_err(CallError::Js),
+        Either::Right((err, _)) => Err(err),
+    }
+}
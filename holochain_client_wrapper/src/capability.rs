@@ -0,0 +1,297 @@
+//! capability secrets & grants, for authorizing zome calls made by other agents.
+//!
+//! grants are made through the admin websocket (`AdminWsCmd::GrantZomeCallCapability`). the
+//! app websocket can't grant capabilities itself, but a `ZomeCallCapGrant` serializes to the
+//! HDK's own `ZomeCallCapGrant`, so it can also be sent as the payload of a zome function which
+//! calls `create_cap_grant`.
+
+use std::{fmt, str::FromStr};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use js_sys::{Object, Reflect, Uint8Array};
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::{
+    AdminWebsocket, AdminWsCmd, AgentPk, AppWebsocket, AppWsCmd, CallError, CellId,
+    DeserializeFromJsObj, SerializeToJsObj,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+// CapSecret
+////////////////////////////////////////////////////////////////////////////////
+
+/// the secret a caller presents to use a transferable or assigned capability.
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct CapSecret([u8; CapSecret::LEN]);
+
+impl CapSecret {
+    pub const LEN: usize = 64;
+
+    /// a fresh secret from the platform's CSPRNG (`crypto.getRandomValues` on wasm).
+    pub fn random() -> CapSecret {
+        let mut bytes = [0; CapSecret::LEN];
+        getrandom::getrandom(&mut bytes).expect("a source of randomness to be available");
+        CapSecret(bytes)
+    }
+
+    pub fn as_bytes(&self) -> &[u8; CapSecret::LEN] {
+        &self.0
+    }
+
+    pub fn to_base64(&self) -> String {
+        STANDARD.encode(self.0)
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<CapSecret, String> {
+        let bytes = STANDARD
+            .decode(encoded)
+            .map_err(|err| format!("invalid base64 cap secret: {}", err))?;
+        CapSecret::try_from(bytes.as_slice())
+    }
+}
+
+impl From<[u8; CapSecret::LEN]> for CapSecret {
+    fn from(bytes: [u8; CapSecret::LEN]) -> Self {
+        CapSecret(bytes)
+    }
+}
+
+impl TryFrom<&[u8]> for CapSecret {
+    type Error = String;
+
+    fn try_from(bytes: &[u8]) -> Result<Self, String> {
+        bytes.try_into().map(CapSecret).map_err(|_| {
+            format!(
+                "cap secret must be {} bytes, not {}",
+                CapSecret::LEN,
+                bytes.len()
+            )
+        })
+    }
+}
+
+impl FromStr for CapSecret {
+    type Err = String;
+
+    fn from_str(encoded: &str) -> Result<Self, String> {
+        CapSecret::from_base64(encoded)
+    }
+}
+
+/// deliberately doesn't print the secret.
+impl fmt::Debug for CapSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CapSecret(..)")
+    }
+}
+
+impl SerializeToJsObj for CapSecret {
+    fn serialize_to_js_obj(self) -> JsValue {
+        Uint8Array::from(&self.0[..]).into()
+    }
+}
+
+impl DeserializeFromJsObj for CapSecret {
+    fn deserialize_from_js_obj(v: JsValue) -> Self {
+        let bytes: Uint8Array = v.dyn_into().expect("Uint8Array conversion to succeed");
+        CapSecret::try_from(bytes.to_vec().as_slice()).expect("cap secret to be 64 bytes")
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// grants
+////////////////////////////////////////////////////////////////////////////////
+
+/// who may use a capability.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CapAccess {
+    /// anyone.
+    Unrestricted,
+    /// anyone holding the secret.
+    Transferable { secret: CapSecret },
+    /// the assignees, who must also present the secret.
+    Assigned {
+        secret: CapSecret,
+        assignees: Vec<AgentPk>,
+    },
+}
+
+impl CapAccess {
+    /// transferable access with a fresh secret.
+    pub fn transferable() -> CapAccess {
+        CapAccess::Transferable {
+            secret: CapSecret::random(),
+        }
+    }
+
+    /// access for `assignees`, with a fresh secret.
+    pub fn assigned(assignees: Vec<AgentPk>) -> CapAccess {
+        CapAccess::Assigned {
+            secret: CapSecret::random(),
+            assignees,
+        }
+    }
+
+    /// the secret callers need to present, if any.
+    pub fn secret(&self) -> Option<&CapSecret> {
+        match self {
+            CapAccess::Unrestricted => None,
+            CapAccess::Transferable { secret } | CapAccess::Assigned { secret, .. } => Some(secret),
+        }
+    }
+}
+
+/// serializes externally tagged, like the HDK: `"Unrestricted"`, `{ Transferable: { secret } }`
+/// or `{ Assigned: { secret, assignees } }`.
+impl SerializeToJsObj for CapAccess {
+    fn serialize_to_js_obj(self) -> JsValue {
+        let (tag, fields) = match self {
+            CapAccess::Unrestricted => return JsValue::from_str("Unrestricted"),
+            CapAccess::Transferable { secret } => (
+                "Transferable",
+                vec![("secret", secret.serialize_to_js_obj())],
+            ),
+            CapAccess::Assigned { secret, assignees } => (
+                "Assigned",
+                vec![
+                    ("secret", secret.serialize_to_js_obj()),
+                    ("assignees", assignees.serialize_to_js_obj()),
+                ],
+            ),
+        };
+        let inner: JsValue = Object::new().into();
+        for (key, val) in fields {
+            assert!(Reflect::set(&inner, &JsValue::from_str(key), &val)
+                .expect("object field set to succeed"));
+        }
+        let outer: JsValue = Object::new().into();
+        assert!(Reflect::set(&outer, &JsValue::from_str(tag), &inner)
+            .expect("object field set to succeed"));
+        outer
+    }
+}
+
+/// a grant of `access` to call `functions`, as `(zome name, function name)` pairs.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ZomeCallCapGrant {
+    /// a label for the grant, e.g. to find it again.
+    pub tag: String,
+    pub access: CapAccess,
+    pub functions: Vec<(String, String)>,
+}
+
+impl ZomeCallCapGrant {
+    pub fn new(
+        tag: impl Into<String>,
+        access: CapAccess,
+        functions: impl IntoIterator<Item = (impl Into<String>, impl Into<String>)>,
+    ) -> Self {
+        ZomeCallCapGrant {
+            tag: tag.into(),
+            access,
+            functions: functions
+                .into_iter()
+                .map(|(zome_name, fn_name)| (zome_name.into(), fn_name.into()))
+                .collect(),
+        }
+    }
+}
+
+impl SerializeToJsObj for ZomeCallCapGrant {
+    fn serialize_to_js_obj(self) -> JsValue {
+        let val: JsValue = Object::new().into();
+        for (key, field) in [
+            ("tag", self.tag.serialize_to_js_obj()),
+            ("access", self.access.serialize_to_js_obj()),
+            ("functions", self.functions.serialize_to_js_obj()),
+        ] {
+            assert!(Reflect::set(&val, &JsValue::from_str(key), &field)
+                .expect("object field set to succeed"));
+        }
+        val
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// helpers
+////////////////////////////////////////////////////////////////////////////////
+
+impl AdminWebsocket {
+    /// lets anyone call `functions` in `cell_id`.
+    pub async fn grant_unrestricted(
+        &self,
+        cell_id: CellId,
+        tag: impl Into<String>,
+        functions: Vec<(String, String)>,
+    ) -> Result<(), CallError> {
+        let grant = ZomeCallCapGrant::new(tag, CapAccess::Unrestricted, functions);
+        self.grant_capability(cell_id, grant).await.map(drop)
+    }
+
+    /// lets whoever holds the returned secret call `functions` in `cell_id`.
+    pub async fn grant_transferable(
+        &self,
+        cell_id: CellId,
+        tag: impl Into<String>,
+        functions: Vec<(String, String)>,
+    ) -> Result<CapSecret, CallError> {
+        let grant = ZomeCallCapGrant::new(tag, CapAccess::transferable(), functions);
+        self.grant_capability(cell_id, grant)
+            .await
+            .map(|secret| secret.expect("transferable access to have a secret"))
+    }
+
+    /// lets `assignees` call `functions` in `cell_id`, presenting the returned secret.
+    pub async fn grant_assigned(
+        &self,
+        cell_id: CellId,
+        tag: impl Into<String>,
+        functions: Vec<(String, String)>,
+        assignees: Vec<AgentPk>,
+    ) -> Result<CapSecret, CallError> {
+        let grant = ZomeCallCapGrant::new(tag, CapAccess::assigned(assignees), functions);
+        self.grant_capability(cell_id, grant)
+            .await
+            .map(|secret| secret.expect("assigned access to have a secret"))
+    }
+
+    /// makes `grant` in `cell_id`, returning its secret (if it has one).
+    pub async fn grant_capability(
+        &self,
+        cell_id: CellId,
+        grant: ZomeCallCapGrant,
+    ) -> Result<Option<CapSecret>, CallError> {
+        let secret = grant.access.secret().cloned();
+        self.call(AdminWsCmd::GrantZomeCallCapability {
+            cell_id,
+            cap_grant: grant,
+        })
+        .await?;
+        Ok(secret)
+    }
+}
+
+impl AppWebsocket {
+    /// makes `grant` through the app's own `zome_name`/`fn_name`, which should take a
+    /// `ZomeCallCapGrant` and `create_cap_grant` it. returns the grant's secret (if it has one).
+    pub async fn grant_capability(
+        &self,
+        cell_id: CellId,
+        zome_name: impl Into<String>,
+        fn_name: impl Into<String>,
+        provenance: AgentPk,
+        grant: ZomeCallCapGrant,
+    ) -> Result<Option<CapSecret>, CallError> {
+        let secret = grant.access.secret().cloned();
+        self.call(AppWsCmd::CallZome {
+            cell_id,
+            zome_name: zome_name.into(),
+            fn_name: fn_name.into(),
+            payload: grant.serialize_to_js_obj(),
+            provenance,
+            cap: None,
+        })
+        .await?;
+        Ok(secret)
+    }
+}
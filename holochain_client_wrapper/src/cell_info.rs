@@ -0,0 +1,287 @@
+//! the cells of an installed app, by role: `AppInfo.cell_info`.
+//!
+//! each role has its provisioned cell (or a stem, if it's deferred), followed by any clones.
+//! conductors which predate `cell_info` only report a flat `cell_data` list of role ids & cell ids;
+//! `AppInfo`s parsed from those put a clone (a `role.N` role id) under its base role, and leave
+//! the details such conductors don't report (DNA modifiers, original DNA hashes) unset.
+
+use std::collections::HashMap;
+
+use js_sys::{Object, Reflect};
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::{
+    AppInfo, CellId, CellIdRoleId, CellIdRoleIdVec, CloneId, ClonedCell, DeserializeFromJsObj,
+    DnaHash, InstalledCell, SerializeToJsObj,
+};
+
+pub type RoleName = String;
+
+////////////////////////////////////////////////////////////////////////////////
+// data types
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CellInfo {
+    /// the cell installed for the role.
+    Provisioned(ProvisionedCell),
+    Cloned(ClonedCell),
+    /// a role whose cell hasn't been created yet, e.g. one provisioned on demand.
+    Stem(StemCell),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProvisionedCell {
+    pub cell_id: CellId,
+    /// `None` from conductors which don't report it.
+    pub dna_modifiers: Option<DnaModifiers>,
+    pub name: String,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StemCell {
+    pub original_dna_hash: DnaHash,
+    pub dna_modifiers: DnaModifiers,
+    pub name: Option<String>,
+}
+
+/// what distinguishes a cell's DNA from others built from the same code.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DnaModifiers {
+    pub network_seed: String,
+    pub properties: JsValue,
+    /// microseconds since the unix epoch.
+    pub origin_time: i64,
+}
+
+impl CellInfo {
+    /// `None` for stems, which have no cell yet.
+    pub fn cell_id(&self) -> Option<&CellId> {
+        match self {
+            CellInfo::Provisioned(cell) => Some(&cell.cell_id),
+            CellInfo::Cloned(cell) => Some(&cell.cell_id),
+            CellInfo::Stem(_) => None,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// AppInfo helpers
+////////////////////////////////////////////////////////////////////////////////
+
+impl AppInfo {
+    /// every callable cell (provisioned cells & enabled clones) as the flat list conductors used
+    /// to report, sorted by role. clones are listed under their clone id, e.g. `chat.0`.
+    pub fn cell_data(&self) -> CellIdRoleIdVec {
+        let mut cell_data: CellIdRoleIdVec = self
+            .cell_info
+            .iter()
+            .flat_map(|(role_name, cells)| {
+                cells.iter().filter_map(move |cell| match cell {
+                    CellInfo::Provisioned(cell) => Some(CellIdRoleId {
+                        cell_id: cell.cell_id.clone(),
+                        role_id: role_name.clone(),
+                    }),
+                    CellInfo::Cloned(cell) if cell.enabled => Some(CellIdRoleId {
+                        cell_id: cell.cell_id.clone(),
+                        role_id: cell.clone_id.to_string(),
+                    }),
+                    _ => None,
+                })
+            })
+            .collect();
+        cell_data.sort_by(|a, b| a.role_id.cmp(&b.role_id));
+        cell_data
+    }
+
+    /// the cells installed with the app, i.e. not clones or stems, sorted by role.
+    pub fn provisioned_cells(&self) -> Vec<InstalledCell> {
+        self.cell_data()
+            .into_iter()
+            .filter(|cell| self.cell_info.contains_key(&cell.role_id))
+            .collect()
+    }
+
+    /// the clones of `role_name`'s cell, enabled or not.
+    pub fn clone_cells(&self, role_name: &str) -> Vec<ClonedCell> {
+        self.cell_info
+            .get(role_name)
+            .into_iter()
+            .flatten()
+            .filter_map(|cell| match cell {
+                CellInfo::Cloned(cell) => Some(cell.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// the provisioned cell playing `role_name`, or the enabled clone with that clone id.
+    pub fn cell_id(&self, role_name: &str) -> Option<CellId> {
+        self.cell_data()
+            .into_iter()
+            .find(|cell| cell.role_id == role_name)
+            .map(|cell| cell.cell_id)
+    }
+
+    /// `cell_info` for a conductor's flat `cell_data`.
+    pub(crate) fn cell_info_from_cell_data(
+        cell_data: CellIdRoleIdVec,
+    ) -> HashMap<RoleName, Vec<CellInfo>> {
+        let mut cell_info: HashMap<RoleName, Vec<CellInfo>> = HashMap::new();
+        for CellIdRoleId { cell_id, role_id } in cell_data {
+            let (role_name, cell) = match role_id.parse::<CloneId>() {
+                Ok(clone_id) => (
+                    clone_id.role_id.clone(),
+                    CellInfo::Cloned(ClonedCell {
+                        cell_id,
+                        name: role_id,
+                        clone_id,
+                        original_dna_hash: None,
+                        dna_modifiers: None,
+                        enabled: true,
+                    }),
+                ),
+                Err(_) => (
+                    role_id.clone(),
+                    CellInfo::Provisioned(ProvisionedCell {
+                        cell_id,
+                        dna_modifiers: None,
+                        name: role_id,
+                    }),
+                ),
+            };
+            cell_info.entry(role_name).or_default().push(cell);
+        }
+        // provisioned cells first, as the conductor lists them.
+        for cells in cell_info.values_mut() {
+            cells.sort_by_key(|cell| !matches!(cell, CellInfo::Provisioned(_)));
+        }
+        cell_info
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// serialization
+////////////////////////////////////////////////////////////////////////////////
+
+fn get(v: &JsValue, key: &str) -> JsValue {
+    Reflect::get(v, &JsValue::from_str(key)).expect("object field get to succeed")
+}
+
+fn object(fields: Vec<(&str, JsValue)>) -> JsValue {
+    let val: JsValue = Object::new().into();
+    for (key, field) in fields {
+        assert!(Reflect::set(&val, &JsValue::from_str(key), &field)
+            .expect("object field set to succeed"));
+    }
+    val
+}
+
+impl SerializeToJsObj for DnaModifiers {
+    fn serialize_to_js_obj(self) -> JsValue {
+        object(vec![
+            ("network_seed", self.network_seed.serialize_to_js_obj()),
+            ("properties", self.properties),
+            ("origin_time", self.origin_time.serialize_to_js_obj()),
+        ])
+    }
+}
+
+impl DeserializeFromJsObj for DnaModifiers {
+    fn deserialize_from_js_obj(v: JsValue) -> Self {
+        DnaModifiers {
+            network_seed: String::deserialize_from_js_obj(get(&v, "network_seed")),
+            properties: get(&v, "properties"),
+            origin_time: i64::deserialize_from_js_obj(get(&v, "origin_time")),
+        }
+    }
+}
+
+/// externally tagged, like the conductor: `{ provisioned: { .. } }`, `{ cloned: { .. } }` or
+/// `{ stem: { .. } }`.
+impl SerializeToJsObj for CellInfo {
+    fn serialize_to_js_obj(self) -> JsValue {
+        let (tag, cell) = match self {
+            CellInfo::Provisioned(cell) => (
+                "provisioned",
+                object(vec![
+                    ("cell_id", cell.cell_id.serialize_to_js_obj()),
+                    ("dna_modifiers", cell.dna_modifiers.serialize_to_js_obj()),
+                    ("name", cell.name.serialize_to_js_obj()),
+                ]),
+            ),
+            CellInfo::Cloned(cell) => ("cloned", cell.serialize_to_js_obj()),
+            CellInfo::Stem(cell) => (
+                "stem",
+                object(vec![
+                    (
+                        "original_dna_hash",
+                        cell.original_dna_hash.serialize_to_js_obj(),
+                    ),
+                    ("dna_modifiers", cell.dna_modifiers.serialize_to_js_obj()),
+                    ("name", cell.name.serialize_to_js_obj()),
+                ]),
+            ),
+        };
+        object(vec![(tag, cell)])
+    }
+}
+
+impl DeserializeFromJsObj for CellInfo {
+    fn deserialize_from_js_obj(v: JsValue) -> Self {
+        let obj: &Object = v.dyn_ref().expect("Object conversion to succeed");
+        let tag = String::deserialize_from_js_obj(Object::keys(obj).get(0));
+        let cell = get(&v, &tag);
+        match tag.as_str() {
+            "provisioned" => CellInfo::Provisioned(ProvisionedCell {
+                cell_id: CellId::deserialize_from_js_obj(get(&cell, "cell_id")),
+                dna_modifiers: Option::<DnaModifiers>::deserialize_from_js_obj(get(
+                    &cell,
+                    "dna_modifiers",
+                )),
+                name: String::deserialize_from_js_obj(get(&cell, "name")),
+            }),
+            "cloned" => CellInfo::Cloned(ClonedCell::deserialize_from_js_obj(cell)),
+            "stem" => CellInfo::Stem(StemCell {
+                original_dna_hash: DnaHash::deserialize_from_js_obj(get(
+                    &cell,
+                    "original_dna_hash",
+                )),
+                dna_modifiers: DnaModifiers::deserialize_from_js_obj(get(&cell, "dna_modifiers")),
+                name: Option::<String>::deserialize_from_js_obj(get(&cell, "name")),
+            }),
+            other => panic!("CellInfo: impossible: received unknown tag: {}", other),
+        }
+    }
+}
+
+impl SerializeToJsObj for HashMap<RoleName, Vec<CellInfo>> {
+    fn serialize_to_js_obj(self) -> JsValue {
+        let val: JsValue = Object::new().into();
+        for (role_name, cells) in self {
+            assert!(Reflect::set(
+                &val,
+                &JsValue::from_str(&role_name),
+                &cells.serialize_to_js_obj(),
+            )
+            .expect("object field set to succeed"));
+        }
+        val
+    }
+}
+
+impl DeserializeFromJsObj for HashMap<RoleName, Vec<CellInfo>> {
+    fn deserialize_from_js_obj(v: JsValue) -> Self {
+        let obj: &Object = v.dyn_ref().expect("Object conversion to succeed");
+        Object::keys(obj)
+            .iter()
+            .map(|role_name| {
+                let cells = Reflect::get(&v, &role_name).expect("object field get to succeed");
+                (
+                    String::deserialize_from_js_obj(role_name),
+                    Vec::<CellInfo>::deserialize_from_js_obj(cells),
+                )
+            })
+            .collect()
+    }
+}
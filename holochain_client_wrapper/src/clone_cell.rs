@@ -0,0 +1,252 @@
+//! clone cells: copies of an app's provisioned cell, with modified DNA properties or network seed.
+//!
+//! clones are created, disabled (archived) & re-enabled through the app websocket, and archived
+//! clones are deleted for good through the admin websocket. a clone's role id is its base role's
+//! followed by its index, e.g. `chat.0`; it's listed in `AppInfo.cell_info` under its base role,
+//! after the provisioned cell.
+
+use std::{fmt, str::FromStr};
+
+use js_sys::{Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::{
+    AppClient, AppWsCmd, AppWsCmdResponse, CallError, CellId, CellIdRoleId, DeserializeFromJsObj,
+    DnaHash, DnaModifiers, SerializeToJsObj,
+};
+
+/// a cell's id & role id, as conductors used to list them in `AppInfo.cell_data`.
+pub type InstalledCell = CellIdRoleId;
+
+////////////////////////////////////////////////////////////////////////////////
+// CloneId
+////////////////////////////////////////////////////////////////////////////////
+
+/// a clone cell's role id: `role_id.index`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CloneId {
+    /// the role of the cell it's a clone of.
+    pub role_id: String,
+    pub index: u32,
+}
+
+impl CloneId {
+    pub fn new(role_id: impl Into<String>, index: u32) -> Self {
+        CloneId {
+            role_id: role_id.into(),
+            index,
+        }
+    }
+}
+
+impl fmt::Display for CloneId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.role_id, self.index)
+    }
+}
+
+impl FromStr for CloneId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let not_a_clone_id = || format!("{} isn't a clone id (role.N)", s);
+        let (role_id, index) = s.rsplit_once('.').ok_or_else(not_a_clone_id)?;
+        if role_id.is_empty() {
+            return Err(not_a_clone_id());
+        }
+        let index = index.parse().map_err(|_| not_a_clone_id())?;
+        Ok(CloneId::new(role_id, index))
+    }
+}
+
+impl SerializeToJsObj for CloneId {
+    fn serialize_to_js_obj(self) -> JsValue {
+        self.to_string().into()
+    }
+}
+
+impl DeserializeFromJsObj for CloneId {
+    fn deserialize_from_js_obj(v: JsValue) -> Self {
+        String::deserialize_from_js_obj(v)
+            .parse()
+            .expect("clone id to parse")
+    }
+}
+
+/// which clone to disable or enable.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CloneCellId {
+    CloneId(CloneId),
+    CellId(CellId),
+}
+
+impl SerializeToJsObj for CloneCellId {
+    fn serialize_to_js_obj(self) -> JsValue {
+        match self {
+            CloneCellId::CloneId(clone_id) => clone_id.serialize_to_js_obj(),
+            CloneCellId::CellId(cell_id) => cell_id.serialize_to_js_obj(),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// requests & results
+////////////////////////////////////////////////////////////////////////////////
+
+/// what makes a clone's DNA differ from the original's. unset fields are inherited.
+#[derive(Clone, Debug, Default)]
+pub struct DnaModifiersOpt {
+    pub network_seed: Option<String>,
+    pub properties: Option<JsValue>,
+    /// microseconds since the unix epoch.
+    pub origin_time: Option<i64>,
+}
+
+impl SerializeToJsObj for DnaModifiersOpt {
+    fn serialize_to_js_obj(self) -> JsValue {
+        let val: JsValue = Object::new().into();
+        for (key, field) in [
+            ("network_seed", self.network_seed.serialize_to_js_obj()),
+            ("properties", self.properties.serialize_to_js_obj()),
+            ("origin_time", self.origin_time.serialize_to_js_obj()),
+        ] {
+            assert!(Reflect::set(&val, &JsValue::from_str(key), &field)
+                .expect("object field set to succeed"));
+        }
+        val
+    }
+}
+
+/// a clone cell, as created or enabled.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ClonedCell {
+    pub cell_id: CellId,
+    pub clone_id: CloneId,
+    /// the hash of the DNA it's a clone of. `None` from conductors which don't report it.
+    pub original_dna_hash: Option<DnaHash>,
+    /// `None` from conductors which don't report it.
+    pub dna_modifiers: Option<DnaModifiers>,
+    /// the clone id, from conductors which don't report a name.
+    pub name: String,
+    /// whether it's callable, i.e. not archived.
+    pub enabled: bool,
+}
+
+impl SerializeToJsObj for ClonedCell {
+    fn serialize_to_js_obj(self) -> JsValue {
+        let val: JsValue = Object::new().into();
+        for (key, field) in [
+            ("cell_id", self.cell_id.serialize_to_js_obj()),
+            ("clone_id", self.clone_id.serialize_to_js_obj()),
+            (
+                "original_dna_hash",
+                self.original_dna_hash.serialize_to_js_obj(),
+            ),
+            ("dna_modifiers", self.dna_modifiers.serialize_to_js_obj()),
+            ("name", self.name.serialize_to_js_obj()),
+            ("enabled", JsValue::from_bool(self.enabled)),
+        ] {
+            assert!(Reflect::set(&val, &JsValue::from_str(key), &field)
+                .expect("object field set to succeed"));
+        }
+        val
+    }
+}
+
+/// conductors which predate `clone_id` respond with an `InstalledCell`, whose `role_id` is the
+/// clone id.
+impl DeserializeFromJsObj for ClonedCell {
+    fn deserialize_from_js_obj(v: JsValue) -> Self {
+        let get = |key: &str| {
+            Reflect::get(&v, &JsValue::from_str(key)).expect("object field get to succeed")
+        };
+        let clone_id = match get("clone_id") {
+            clone_id if clone_id.is_string() => clone_id,
+            _ => get("role_id"),
+        };
+        let clone_id = CloneId::deserialize_from_js_obj(clone_id);
+        ClonedCell {
+            cell_id: CellId::deserialize_from_js_obj(get("cell_id")),
+            original_dna_hash: Option::<DnaHash>::deserialize_from_js_obj(get("original_dna_hash")),
+            dna_modifiers: Option::<DnaModifiers>::deserialize_from_js_obj(get("dna_modifiers")),
+            name: get("name")
+                .as_string()
+                .unwrap_or_else(|| clone_id.to_string()),
+            enabled: get("enabled").as_bool().unwrap_or(true),
+            clone_id,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// AppClient
+////////////////////////////////////////////////////////////////////////////////
+
+/// each of these invalidates the cached `AppInfo`, so the clone is found (or not) by role.
+impl AppClient {
+    /// clones `role_id`'s cell, applying `modifiers`.
+    pub async fn create_clone_cell(
+        &self,
+        role_id: impl Into<String>,
+        modifiers: DnaModifiersOpt,
+        name: Option<String>,
+    ) -> Result<ClonedCell, CallError> {
+        let resp = self
+            .ws()
+            .call(AppWsCmd::CreateCloneCell {
+                app_id: self.installed_app_id().into(),
+                role_id: role_id.into(),
+                modifiers,
+                membrane_proof: None,
+                name,
+            })
+            .await?;
+        self.invalidate();
+        match resp {
+            AppWsCmdResponse::CreateCloneCell(cell) => Ok(cell),
+            other => panic!(
+                "AppClient::create_clone_cell: impossible: received {:?}",
+                other
+            ),
+        }
+    }
+
+    /// archives a clone; it can be enabled again until its archive is deleted.
+    pub async fn disable_clone_cell(&self, clone_cell_id: CloneCellId) -> Result<(), CallError> {
+        self.ws()
+            .call(AppWsCmd::DisableCloneCell {
+                app_id: self.installed_app_id().into(),
+                clone_cell_id,
+            })
+            .await?;
+        self.invalidate();
+        Ok(())
+    }
+
+    /// restores an archived clone.
+    pub async fn enable_clone_cell(
+        &self,
+        clone_cell_id: CloneCellId,
+    ) -> Result<ClonedCell, CallError> {
+        let resp = self
+            .ws()
+            .call(AppWsCmd::EnableCloneCell {
+                app_id: self.installed_app_id().into(),
+                clone_cell_id,
+            })
+            .await?;
+        self.invalidate();
+        match resp {
+            AppWsCmdResponse::EnableCloneCell(cell) => Ok(cell),
+            other => panic!(
+                "AppClient::enable_clone_cell: impossible: received {:?}",
+                other
+            ),
+        }
+    }
+
+    /// the clones of `role_id`'s cell, from a fresh `AppInfo`.
+    pub async fn clone_cells(&self, role_id: &str) -> Result<Vec<ClonedCell>, CallError> {
+        Ok(self.refresh().await?.clone_cells(role_id))
+    }
+}
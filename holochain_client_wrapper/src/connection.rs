@@ -0,0 +1,104 @@
+//! ownership of the JS client behind a websocket.
+//!
+//! every clone of an `AdminWebsocket`/`AppWebsocket` shares one `Connection`. `close` shuts it
+//! down explicitly, and failing that, the last clone to be dropped closes it.
+
+use std::{cell::Cell, future::Future, rc::Rc};
+
+use futures::{
+    future::{self, Either},
+    StreamExt,
+};
+use js_sys::{Function, Promise, Reflect};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::JsFuture;
+
+use crate::{broadcast::Subscribers, CallError};
+
+#[derive(Debug)]
+pub(crate) struct Connection {
+    pub(crate) js_ws: JsValue,
+    closed: Cell<bool>,
+    /// set when the connection is handed over to JS, which then owns its lifetime.
+    released: Cell<bool>,
+    /// told when `close` is called, so pending calls can bail out.
+    closing: Rc<Subscribers<()>>,
+}
+
+impl Connection {
+    pub(crate) fn new(js_ws: JsValue) -> Rc<Connection> {
+        Rc::new(Connection {
+            js_ws,
+            closed: Cell::new(false),
+            released: Cell::new(false),
+            closing: Rc::default(),
+        })
+    }
+
+    pub(crate) fn is_closed(&self) -> bool {
+        self.closed.get()
+    }
+
+    /// drives a call on this connection, failing it with `CallError::Closed` if the connection is
+    /// (or gets) closed first.
+    pub(crate) async fn guard<T>(
+        &self,
+        call: impl Future<Output = Result<T, CallError>>,
+    ) -> Result<T, CallError> {
+        if self.is_closed() {
+            return Err(CallError::Closed);
+        }
+        let mut closing = self.closing.subscribe();
+        match future::select(Box::pin(call), closing.next()).await {
+            Either::Left((res, _)) => res,
+            Either::Right(_) => Err(CallError::Closed),
+        }
+    }
+
+    /// rejects pending calls, then waits for the JS client to close its socket. closing twice is a
+    /// no-op.
+    pub(crate) async fn close(&self) -> Result<(), CallError> {
+        if self.closed.replace(true) {
+            return Ok(());
+        }
+        self.closing.publish(());
+        match close_js(&self.js_ws)? {
+            Some(promise) => JsFuture::from(promise)
+                .await
+                .map(drop)
+                .map_err(CallError::Js),
+            None => Ok(()),
+        }
+    }
+
+    /// gives up ownership if `connection` is the last reference, so dropping it leaves the socket
+    /// open.
+    pub(crate) fn release(connection: Rc<Connection>) {
+        if let Ok(connection) = Rc::try_unwrap(connection) {
+            connection.released.set(true);
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if !self.closed.get() && !self.released.get() {
+            let _ = close_js(&self.js_ws);
+        }
+    }
+}
+
+/// calls `client.close()` on a `holochain-client-js` websocket. clients without one (e.g. replay
+/// backends) have nothing to close.
+fn close_js(js_ws: &JsValue) -> Result<Option<Promise>, CallError> {
+    let client = Reflect::get(js_ws, &JsValue::from_str("client"))?;
+    if !client.is_object() {
+        return Ok(None);
+    }
+    let close = match Reflect::get(&client, &JsValue::from_str("close"))?.dyn_into::<Function>() {
+        Ok(close) => close,
+        Err(_) => return Ok(None),
+    };
+    let res = close.call0(&client)?;
+    Ok(res.dyn_into::<Promise>().ok())
+}
@@ -0,0 +1,156 @@
+//! errors reported by the conductor itself, as opposed to by the JS client or this crate.
+
+use std::fmt;
+
+use js_sys::Reflect;
+use wasm_bindgen::prelude::*;
+
+use crate::CallError;
+
+/// an error response from the conductor.
+#[derive(Clone, Debug)]
+pub struct ConductorError {
+    /// the error's wire `type`, e.g. `internal_error` or `ribosome_error`.
+    pub error_type: String,
+    /// the error's details, usually a message string.
+    pub data: JsValue,
+}
+
+impl ConductorError {
+    /// parses a rejection from `holochain-client-js`. the conductor's `{ type, data }` error is
+    /// either the rejection itself or, as the client passes the whole response along, its `data`
+    /// under `{ type: "error" }`.
+    pub fn from_js(val: &JsValue) -> Option<ConductorError> {
+        let get = |obj: &JsValue, key: &str| Reflect::get(obj, &JsValue::from_str(key)).ok();
+        if !val.is_object() {
+            return None;
+        }
+        let mut err = val.clone();
+        if get(&err, "type")?.as_string()? == "error" {
+            err = get(&err, "data").filter(|data| data.is_object())?;
+        }
+        Some(ConductorError {
+            error_type: get(&err, "type")?.as_string()?,
+            data: get(&err, "data").unwrap_or(JsValue::UNDEFINED),
+        })
+    }
+}
+
+impl fmt::Display for ConductorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.data.as_string() {
+            Some(message) => write!(f, "{}: {}", self.error_type, message),
+            None => write!(f, "{}: {:?}", self.error_type, self.data),
+        }
+    }
+}
+
+impl std::error::Error for ConductorError {}
+
+impl CallError {
+    /// the conductor's error, if that's why the call was rejected.
+    pub fn conductor_error(&self) -> Option<ConductorError> {
+        match self {
+            CallError::Js(val) => ConductorError::from_js(val),
+            _ => None,
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// ZomeCallError
+////////////////////////////////////////////////////////////////////////////////
+
+/// why a zome call failed.
+///
+/// the conductor only reports most zome failures as message strings, so the variants below are
+/// recognised from the Rust `Debug` output embedded in those messages.
+#[derive(Clone, Debug)]
+pub enum ZomeCallError {
+    /// the ribosome failed the call, for a reason not covered below. carries the message.
+    RibosomeError(String),
+    /// the zome itself returned an error, e.g. via `wasm_error!(WasmErrorInner::Guest(..))`.
+    WasmError { guest: String },
+    /// the caller doesn't hold a capability for the function.
+    CapabilityUnauthorized,
+    /// something the call tried to commit failed validation, e.g. in an integrity zome.
+    ValidationFailed { reason: String },
+    /// the conductor failed to run the call. carries the message.
+    Internal(String),
+    /// the call never got a conductor error: it timed out, was cancelled, or the JS client failed.
+    Call(CallError),
+}
+
+impl ZomeCallError {
+    pub fn from_conductor_error(err: &ConductorError) -> ZomeCallError {
+        let message = err
+            .data
+            .as_string()
+            .unwrap_or_else(|| format!("{:?}", err.data));
+        if err.error_type == "zome_call_unauthorized" {
+            return ZomeCallError::CapabilityUnauthorized;
+        }
+        // validation failures surface as `internal_error`s (as an `InvalidCommit` source chain
+        // error) or `ribosome_error`s, depending on where they're caught.
+        if let Some(reason) = ["InvalidCommit(", "Invalid("]
+            .iter()
+            .find_map(|marker| debug_str_after(&message, marker))
+        {
+            return ZomeCallError::ValidationFailed { reason };
+        }
+        if let Some(guest) = debug_str_after(&message, "Guest(") {
+            return ZomeCallError::WasmError { guest };
+        }
+        match err.error_type.as_str() {
+            "ribosome_error" => ZomeCallError::RibosomeError(message),
+            _ => ZomeCallError::Internal(message),
+        }
+    }
+}
+
+impl From<CallError> for ZomeCallError {
+    fn from(err: CallError) -> Self {
+        match err.conductor_error() {
+            Some(conductor_error) => ZomeCallError::from_conductor_error(&conductor_error),
+            None => ZomeCallError::Call(err),
+        }
+    }
+}
+
+impl fmt::Display for ZomeCallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZomeCallError::RibosomeError(message) => write!(f, "ribosome error: {}", message),
+            ZomeCallError::WasmError { guest } => write!(f, "{}", guest),
+            ZomeCallError::CapabilityUnauthorized => write!(f, "unauthorized zome call"),
+            ZomeCallError::ValidationFailed { reason } => write!(f, "{}", reason),
+            ZomeCallError::Internal(message) => write!(f, "conductor error: {}", message),
+            ZomeCallError::Call(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for ZomeCallError {}
+
+/// the Rust `Debug`-formatted string literal right after the first `marker` in `message`,
+/// unescaped. e.g. `Guest("nope")` with marker `Guest(` gives `nope`.
+fn debug_str_after(message: &str, marker: &str) -> Option<String> {
+    let start = message.find(marker)? + marker.len();
+    let mut chars = message[start..].chars();
+    if chars.next()? != '"' {
+        return None;
+    }
+    let mut unescaped = String::new();
+    loop {
+        match chars.next()? {
+            '"' => return Some(unescaped),
+            '\\' => match chars.next()? {
+                'n' => unescaped.push('\n'),
+                't' => unescaped.push('\t'),
+                'r' => unescaped.push('\r'),
+                escaped => unescaped.push(escaped),
+            },
+            c => unescaped.push(c),
+        }
+    }
+}
@@ -0,0 +1,215 @@
+//! building `AdminWsCmd::InstallApp` from roles, rather than by hand.
+//!
+//! each role's DNA is given either by the hash of an already registered DNA, or by the path of a
+//! DNA file for `AdminWebsocket::install_app` to register first (with the role's network seed &
+//! properties, if any). the builder checks role ids are unique before anything is sent.
+
+use std::{collections::HashSet, fmt};
+
+use crate::{
+    AdminWebsocket, AdminWsCmd, AdminWsCmdResponse, AgentPk, CallError, DnaHash, HashRoleProof,
+    MembraneProof,
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum DnaSource {
+    /// a DNA which is already registered.
+    Hash(DnaHash),
+    /// a DNA file on the conductor's filesystem, registered when installing.
+    Path(String),
+}
+
+/// a role to install, & the DNA to play it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InstallRole {
+    pub role_id: String,
+    pub source: DnaSource,
+    /// only for `DnaSource::Path`: a registered DNA's network seed is already fixed.
+    pub network_seed: Option<String>,
+    /// only for `DnaSource::Path`, likewise.
+    pub properties: Option<String>,
+    pub membrane_proof: Option<MembraneProof>,
+}
+
+impl InstallRole {
+    pub fn hash(role_id: impl Into<String>, hash: DnaHash) -> Self {
+        InstallRole::new(role_id.into(), DnaSource::Hash(hash))
+    }
+
+    pub fn path(role_id: impl Into<String>, path: impl Into<String>) -> Self {
+        InstallRole::new(role_id.into(), DnaSource::Path(path.into()))
+    }
+
+    fn new(role_id: String, source: DnaSource) -> Self {
+        InstallRole {
+            role_id,
+            source,
+            network_seed: None,
+            properties: None,
+            membrane_proof: None,
+        }
+    }
+
+    pub fn network_seed(mut self, network_seed: impl Into<String>) -> Self {
+        self.network_seed = Some(network_seed.into());
+        self
+    }
+
+    pub fn properties(mut self, properties: impl Into<String>) -> Self {
+        self.properties = Some(properties.into());
+        self
+    }
+
+    pub fn membrane_proof(mut self, membrane_proof: impl Into<MembraneProof>) -> Self {
+        self.membrane_proof = Some(membrane_proof.into());
+        self
+    }
+}
+
+#[derive(Clone, Debug)]
+pub enum InstallAppError {
+    EmptyAppId,
+    NoRoles,
+    DuplicateRole(String),
+    /// an app with this id is already installed.
+    AppIdTaken(String),
+    /// this role's DNA is given by hash, but has a network seed or properties to apply.
+    ModifiersWithHash(String),
+    /// `build` was asked for a command, but this role's DNA is given by path & isn't registered.
+    UnregisteredDna(String),
+    Call(CallError),
+}
+
+impl From<CallError> for InstallAppError {
+    fn from(err: CallError) -> Self {
+        InstallAppError::Call(err)
+    }
+}
+
+impl fmt::Display for InstallAppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstallAppError::EmptyAppId => write!(f, "installed app id is empty"),
+            InstallAppError::NoRoles => write!(f, "app has no roles"),
+            InstallAppError::DuplicateRole(role_id) => {
+                write!(f, "role {} is given more than once", role_id)
+            }
+            InstallAppError::AppIdTaken(id) => write!(f, "app {} is already installed", id),
+            InstallAppError::ModifiersWithHash(role_id) => write!(
+                f,
+                "role {}'s DNA is already registered, so can't take a network seed or properties",
+                role_id
+            ),
+            InstallAppError::UnregisteredDna(role_id) => {
+                write!(f, "role {}'s DNA hasn't been registered", role_id)
+            }
+            InstallAppError::Call(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for InstallAppError {}
+
+#[derive(Clone, Debug)]
+pub struct InstallAppBuilder {
+    pub installed_app_id: String,
+    pub agent_key: AgentPk,
+    pub roles: Vec<InstallRole>,
+}
+
+impl InstallAppBuilder {
+    pub fn new(installed_app_id: impl Into<String>, agent_key: AgentPk) -> Self {
+        InstallAppBuilder {
+            installed_app_id: installed_app_id.into(),
+            agent_key,
+            roles: Vec::new(),
+        }
+    }
+
+    /// roles are installed in the order they're added.
+    pub fn role(mut self, role: InstallRole) -> Self {
+        self.roles.push(role);
+        self
+    }
+
+    /// checks everything which can be checked without the conductor.
+    pub fn validate(&self) -> Result<(), InstallAppError> {
+        if self.installed_app_id.is_empty() {
+            return Err(InstallAppError::EmptyAppId);
+        }
+        if self.roles.is_empty() {
+            return Err(InstallAppError::NoRoles);
+        }
+        let mut role_ids = HashSet::new();
+        for role in &self.roles {
+            if !role_ids.insert(&role.role_id) {
+                return Err(InstallAppError::DuplicateRole(role.role_id.clone()));
+            }
+            let has_modifiers = role.network_seed.is_some() || role.properties.is_some();
+            if matches!(role.source, DnaSource::Hash(_)) && has_modifiers {
+                return Err(InstallAppError::ModifiersWithHash(role.role_id.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    /// the `InstallApp` command, once every role's DNA is given by hash. use
+    /// `AdminWebsocket::install_app` to register DNAs given by path, too.
+    pub fn build(self) -> Result<AdminWsCmd, InstallAppError> {
+        self.validate()?;
+        let dnas = self
+            .roles
+            .into_iter()
+            .map(|role| match role.source {
+                DnaSource::Hash(hash) => Ok(HashRoleProof {
+                    hash,
+                    role_id: role.role_id,
+                    membrane_proof: role.membrane_proof,
+                }),
+                DnaSource::Path(_) => Err(InstallAppError::UnregisteredDna(role.role_id)),
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(AdminWsCmd::InstallApp {
+            installed_app_id: self.installed_app_id,
+            agent_key: self.agent_key,
+            dnas,
+        })
+    }
+}
+
+impl AdminWebsocket {
+    /// validates `builder`, checks no app with its id is installed, registers the DNAs given by
+    /// path, then installs the app. DNAs registered before a failure stay registered.
+    pub async fn install_app(
+        &self,
+        mut builder: InstallAppBuilder,
+    ) -> Result<AdminWsCmdResponse, InstallAppError> {
+        builder.validate()?;
+        if self
+            .installed_apps()
+            .await?
+            .iter()
+            .any(|app| app.installed_app_id == builder.installed_app_id)
+        {
+            return Err(InstallAppError::AppIdTaken(builder.installed_app_id));
+        }
+
+        for role in &mut builder.roles {
+            let path = match &role.source {
+                DnaSource::Path(path) => path.clone(),
+                DnaSource::Hash(_) => continue,
+            };
+            let register = AdminWsCmd::RegisterDna {
+                path,
+                uid: role.network_seed.take(),
+                properties: role.properties.take(),
+            };
+            match self.call(register).await? {
+                AdminWsCmdResponse::RegisterDna(hash) => role.source = DnaSource::Hash(hash),
+                other => panic!("install_app: impossible: received {:?}", other),
+            }
+        }
+
+        Ok(self.call(builder.build()?).await?)
+    }
+}
@@ -0,0 +1,312 @@
+//! where signing credentials are kept between calls, and between page loads.
+//!
+//! a `ZomeCallSigner` looks credentials up in a `SigningKeyStore`. `MemoryKeyStore` forgets them
+//! on reload; `EncryptedKeyStore` can be written out as a blob, encrypted with a key derived from
+//! a passphrase, and read back later. neither hands the credentials' secret bytes to the caller.
+//!
+//! stores which keep credentials elsewhere (e.g. IndexedDB, or a native keychain) get them as
+//! `SealedCredentials`: encrypted with a `SealingKey`, so the store only ever handles ciphertext.
+
+use std::{cell::RefCell, fmt, rc::Rc};
+
+use argon2::Argon2;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit},
+    XChaCha20Poly1305, XNonce,
+};
+use js_sys::Uint8Array;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use zeroize::Zeroizing;
+
+use crate::{AgentPk, CellId, DeserializeFromJsObj, DnaHash, SigningCredentials};
+
+////////////////////////////////////////////////////////////////////////////////
+// SigningKeyStore
+////////////////////////////////////////////////////////////////////////////////
+
+/// signing credentials, by cell. stores which persist credentials should keep them sealed (see
+/// `SealingKey::seal`), unsealing them again in `get`.
+pub trait SigningKeyStore {
+    fn get(&self, cell_id: &CellId) -> Option<SigningCredentials>;
+    /// replaces any credentials `cell_id` had.
+    fn set(&self, cell_id: CellId, credentials: SigningCredentials);
+    fn remove(&self, cell_id: &CellId) -> Option<SigningCredentials>;
+}
+
+/// keeps credentials in memory only. clones share the same credentials.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryKeyStore(Rc<RefCell<Vec<(CellId, SigningCredentials)>>>);
+
+impl MemoryKeyStore {
+    pub fn len(&self) -> usize {
+        self.0.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl SigningKeyStore for MemoryKeyStore {
+    fn get(&self, cell_id: &CellId) -> Option<SigningCredentials> {
+        self.0
+            .borrow()
+            .iter()
+            .find(|(id, _)| id == cell_id)
+            .map(|(_, credentials)| credentials.clone())
+    }
+
+    fn set(&self, cell_id: CellId, credentials: SigningCredentials) {
+        self.remove(&cell_id);
+        self.0.borrow_mut().push((cell_id, credentials));
+    }
+
+    fn remove(&self, cell_id: &CellId) -> Option<SigningCredentials> {
+        let mut entries = self.0.borrow_mut();
+        let i = entries.iter().position(|(id, _)| id == cell_id)?;
+        Some(entries.remove(i).1)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// SealedCredentials
+////////////////////////////////////////////////////////////////////////////////
+
+/// a key for sealing credentials with XChaCha20-Poly1305. keeping it (e.g. as a non-extractable
+/// WebCrypto key's wrapped bytes) is up to the store.
+#[derive(Clone)]
+pub struct SealingKey(Zeroizing<[u8; 32]>);
+
+impl SealingKey {
+    /// a fresh key, from the platform's CSPRNG.
+    pub fn generate() -> Self {
+        let mut key = Zeroizing::new([0; 32]);
+        getrandom::getrandom(&mut *key).expect("a source of randomness to be available");
+        SealingKey(key)
+    }
+
+    pub fn from_bytes(key: [u8; 32]) -> Self {
+        SealingKey(Zeroizing::new(key))
+    }
+
+    /// a key derived from `passphrase` & `salt` with argon2id, as `EncryptedKeyStore` does.
+    pub fn from_passphrase(passphrase: &str, salt: &[u8; SALT_LEN]) -> Self {
+        SealingKey(derive_key(passphrase, salt))
+    }
+
+    /// `credentials`, encrypted under a fresh nonce.
+    pub fn seal(&self, credentials: &SigningCredentials) -> SealedCredentials {
+        SealedCredentials(seal(&self.0, &credentials.to_secret_bytes()))
+    }
+
+    /// `None` if `sealed` wasn't sealed with this key, or was tampered with.
+    pub fn unseal(&self, sealed: &SealedCredentials) -> Option<SigningCredentials> {
+        SigningCredentials::from_secret_bytes(&open(&self.0, &sealed.0)?)
+    }
+}
+
+/// deliberately doesn't print the key.
+impl fmt::Debug for SealingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SealingKey").finish_non_exhaustive()
+    }
+}
+
+/// credentials encrypted by `SealingKey::seal`: opaque bytes, safe to persist as they are.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SealedCredentials(Vec<u8>);
+
+impl SealedCredentials {
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        SealedCredentials(bytes)
+    }
+}
+
+/// the nonce followed by the ciphertext.
+fn seal(key: &[u8; 32], plaintext: &[u8]) -> Vec<u8> {
+    let mut nonce = [0; NONCE_LEN];
+    getrandom::getrandom(&mut nonce).expect("a source of randomness to be available");
+    let ciphertext = XChaCha20Poly1305::new(key.into())
+        .encrypt(XNonce::from_slice(&nonce), plaintext)
+        .expect("encryption to succeed");
+    [&nonce[..], &ciphertext].concat()
+}
+
+/// `None` if `sealed` is truncated or doesn't decrypt with `key`.
+fn open(key: &[u8; 32], sealed: &[u8]) -> Option<Zeroizing<Vec<u8>>> {
+    if sealed.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+    XChaCha20Poly1305::new(key.into())
+        .decrypt(XNonce::from_slice(nonce), ciphertext)
+        .ok()
+        .map(Zeroizing::new)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// EncryptedKeyStore
+////////////////////////////////////////////////////////////////////////////////
+
+/// the blob layout: this version byte, the key derivation salt, the nonce, then the ciphertext.
+const BLOB_VERSION: u8 = 1;
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum KeyStoreError {
+    /// the blob isn't one `EncryptedKeyStore::to_blob` wrote.
+    Malformed(String),
+    /// the blob doesn't decrypt: either the passphrase is wrong or the blob was tampered with.
+    WrongPassphrase,
+}
+
+impl fmt::Display for KeyStoreError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyStoreError::Malformed(reason) => write!(f, "malformed key store blob: {}", reason),
+            KeyStoreError::WrongPassphrase => write!(f, "wrong passphrase for key store"),
+        }
+    }
+}
+
+impl std::error::Error for KeyStoreError {}
+
+/// keeps credentials in memory, and writes them out encrypted by `to_blob` for the application
+/// to persist (e.g. in `localStorage`). clones share the same credentials.
+///
+/// the encryption key is derived from the passphrase with argon2id, which deliberately takes a
+/// noticeable moment; it's done once, when the store is created or loaded.
+#[derive(Clone)]
+pub struct EncryptedKeyStore {
+    credentials: MemoryKeyStore,
+    key: Rc<Zeroizing<[u8; 32]>>,
+    salt: [u8; SALT_LEN],
+}
+
+impl fmt::Debug for EncryptedKeyStore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EncryptedKeyStore")
+            .field("credentials", &self.credentials.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// a stored cell's credentials, as encrypted.
+#[derive(Serialize, Deserialize)]
+struct Entry {
+    dna_hash: ByteBuf,
+    agent_pk: ByteBuf,
+    secret: ByteBuf,
+}
+
+impl EncryptedKeyStore {
+    /// an empty store, to be encrypted with `passphrase`.
+    pub fn new(passphrase: &str) -> Self {
+        let mut salt = [0; SALT_LEN];
+        getrandom::getrandom(&mut salt).expect("a source of randomness to be available");
+        EncryptedKeyStore {
+            credentials: MemoryKeyStore::default(),
+            key: Rc::new(derive_key(passphrase, &salt)),
+            salt,
+        }
+    }
+
+    /// loads a store written by `to_blob`. later blobs are encrypted with the same passphrase.
+    pub fn from_blob(blob: &str, passphrase: &str) -> Result<Self, KeyStoreError> {
+        let malformed = |reason: &str| KeyStoreError::Malformed(reason.into());
+        let bytes = STANDARD.decode(blob).map_err(|_| malformed("not base64"))?;
+        let (&version, rest) = bytes.split_first().ok_or_else(|| malformed("empty"))?;
+        if version != BLOB_VERSION {
+            return Err(KeyStoreError::Malformed(format!(
+                "unknown version {}",
+                version
+            )));
+        }
+        if rest.len() < SALT_LEN + NONCE_LEN {
+            return Err(malformed("truncated"));
+        }
+        let (salt, sealed) = rest.split_at(SALT_LEN);
+        let salt: [u8; SALT_LEN] = salt.try_into().expect("a salt-sized slice");
+        let key = derive_key(passphrase, &salt);
+        let plaintext = open(&key, sealed).ok_or(KeyStoreError::WrongPassphrase)?;
+        let entries: Vec<Entry> =
+            rmp_serde::from_slice(&plaintext).map_err(|_| malformed("undecodable credentials"))?;
+        let credentials = MemoryKeyStore::default();
+        for entry in entries {
+            let secret = Zeroizing::new(entry.secret.into_vec());
+            let cell_id = (
+                DnaHash::deserialize_from_js_obj(Uint8Array::from(&entry.dna_hash[..]).into()),
+                AgentPk::deserialize_from_js_obj(Uint8Array::from(&entry.agent_pk[..]).into()),
+            );
+            let cell_credentials = SigningCredentials::from_secret_bytes(&secret)
+                .ok_or_else(|| malformed("invalid credentials"))?;
+            credentials.set(cell_id, cell_credentials);
+        }
+        Ok(EncryptedKeyStore {
+            credentials,
+            key: Rc::new(key),
+            salt,
+        })
+    }
+
+    /// every cell's credentials, encrypted & base64-encoded.
+    pub fn to_blob(&self) -> String {
+        let entries: Vec<Entry> = self
+            .credentials
+            .0
+            .borrow()
+            .iter()
+            .map(|((dna_hash, agent_pk), credentials)| Entry {
+                dna_hash: ByteBuf::from(Uint8Array::new(&dna_hash.0).to_vec()),
+                agent_pk: ByteBuf::from(Uint8Array::new(&agent_pk.0).to_vec()),
+                secret: ByteBuf::from(credentials.to_secret_bytes().to_vec()),
+            })
+            .collect();
+        let plaintext =
+            Zeroizing::new(rmp_serde::to_vec(&entries).expect("msgpack encoding to succeed"));
+        // the copies of the secrets in `entries` are wiped along with it.
+        for mut entry in entries {
+            zeroize::Zeroize::zeroize(entry.secret.as_mut_slice());
+        }
+        let sealed = seal(&self.key, &plaintext);
+        STANDARD.encode([&[BLOB_VERSION][..], &self.salt, &sealed].concat())
+    }
+
+    pub fn len(&self) -> usize {
+        self.credentials.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.credentials.is_empty()
+    }
+}
+
+impl SigningKeyStore for EncryptedKeyStore {
+    fn get(&self, cell_id: &CellId) -> Option<SigningCredentials> {
+        self.credentials.get(cell_id)
+    }
+
+    fn set(&self, cell_id: CellId, credentials: SigningCredentials) {
+        self.credentials.set(cell_id, credentials)
+    }
+
+    fn remove(&self, cell_id: &CellId) -> Option<SigningCredentials> {
+        self.credentials.remove(cell_id)
+    }
+}
+
+/// argon2id, with its default (OWASP recommended) cost.
+fn derive_key(passphrase: &str, salt: &[u8; SALT_LEN]) -> Zeroizing<[u8; 32]> {
+    let mut key = Zeroizing::new([0; 32]);
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut *key)
+        .expect("argon2 parameters to be valid");
+    key
+}
@@ -0,0 +1,147 @@
+//! running inside the Holochain launcher, which injects the app's port & id into the page.
+//!
+//! the launcher sets `window.__HC_LAUNCHER_ENV__` to
+//! `{ APP_INTERFACE_PORT, INSTALLED_APP_ID, ADMIN_INTERFACE_PORT }`. outside it (e.g. during
+//! development), the same values can come from the page's query string
+//! (`?app_port=8888&installed_app_id=app&admin_port=4444`), or from explicit config.
+
+use std::fmt;
+
+use js_sys::{decode_uri_component, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::{connect_app_ws, AppClient};
+
+const LAUNCHER_ENV_KEY: &str = "__HC_LAUNCHER_ENV__";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LauncherEnv {
+    pub app_port: u16,
+    pub installed_app_id: String,
+    /// only known when the launcher grants the page admin access.
+    pub admin_port: Option<u16>,
+}
+
+#[derive(Clone, Debug)]
+pub enum LauncherEnvError {
+    /// there's no launcher env, query params or config to connect with.
+    NotDetected,
+    Connect(String),
+}
+
+impl fmt::Display for LauncherEnvError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LauncherEnvError::NotDetected => write!(f, "no launcher environment found"),
+            LauncherEnvError::Connect(err) => {
+                write!(f, "connecting to the app interface failed: {}", err)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LauncherEnvError {}
+
+impl LauncherEnv {
+    pub fn new(app_port: u16, installed_app_id: impl Into<String>) -> Self {
+        LauncherEnv {
+            app_port,
+            installed_app_id: installed_app_id.into(),
+            admin_port: None,
+        }
+    }
+
+    /// the launcher's injected env, else the page's query params.
+    pub fn detect() -> Option<LauncherEnv> {
+        let global = js_sys::global();
+        let injected = Reflect::get(&global, &JsValue::from_str(LAUNCHER_ENV_KEY)).ok()?;
+        LauncherEnv::from_js(&injected).or_else(|| {
+            let location = Reflect::get(&global, &JsValue::from_str("location")).ok()?;
+            let search = Reflect::get(&location, &JsValue::from_str("search")).ok()?;
+            LauncherEnv::from_query(&search.as_string()?)
+        })
+    }
+
+    /// `detect`, falling back to `config`.
+    pub fn detect_or(config: LauncherEnv) -> LauncherEnv {
+        LauncherEnv::detect().unwrap_or(config)
+    }
+
+    /// an env object as the launcher injects it. `None` if it's missing the app's port or id.
+    pub fn from_js(val: &JsValue) -> Option<LauncherEnv> {
+        if !val.is_object() {
+            return None;
+        }
+        let get = |key: &str| Reflect::get(val, &JsValue::from_str(key)).ok();
+        let port = |key: &str| get(key)?.as_f64().and_then(port_from_f64);
+        Some(LauncherEnv {
+            app_port: port("APP_INTERFACE_PORT")?,
+            installed_app_id: get("INSTALLED_APP_ID")?.as_string()?,
+            admin_port: port("ADMIN_INTERFACE_PORT"),
+        })
+    }
+
+    /// a query string, with or without its leading `?`. `None` if it's missing `app_port` or
+    /// `installed_app_id`.
+    pub fn from_query(search: &str) -> Option<LauncherEnv> {
+        let mut app_port = None;
+        let mut installed_app_id = None;
+        let mut admin_port = None;
+        for pair in search.trim_start_matches('?').split('&') {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let value = decode_uri_component(&value.replace('+', " "))
+                .ok()
+                .map(String::from);
+            match key {
+                "app_port" => app_port = value.and_then(|v| v.parse().ok()),
+                "installed_app_id" => installed_app_id = value,
+                "admin_port" => admin_port = value.and_then(|v| v.parse().ok()),
+                _ => {}
+            }
+        }
+        Some(LauncherEnv {
+            app_port: app_port?,
+            installed_app_id: installed_app_id.filter(|id| !id.is_empty())?,
+            admin_port,
+        })
+    }
+
+    /// the app interface's url. the launcher's conductor always runs on this machine.
+    pub fn app_url(&self) -> String {
+        format!("ws://localhost:{}", self.app_port)
+    }
+
+    /// the admin interface's url, if the page has admin access.
+    pub fn admin_url(&self) -> Option<String> {
+        self.admin_port
+            .map(|port| format!("ws://localhost:{}", port))
+    }
+
+    /// connects to the app interface, returning a client bound to the installed app.
+    pub async fn connect(&self, timeout: Option<u32>) -> Result<AppClient, LauncherEnvError> {
+        let ws = connect_app_ws(self.app_url(), timeout)
+            .await
+            .map_err(LauncherEnvError::Connect)?;
+        Ok(AppClient::new(ws, self.installed_app_id.clone()))
+    }
+}
+
+/// connects to the app the page is running for: the launcher's, else the one named in the query
+/// string, else `config`'s.
+pub async fn connect_from_env(
+    config: Option<LauncherEnv>,
+    timeout: Option<u32>,
+) -> Result<AppClient, LauncherEnvError> {
+    let env = LauncherEnv::detect()
+        .or(config)
+        .ok_or(LauncherEnvError::NotDetected)?;
+    env.connect(timeout).await
+}
+
+fn port_from_f64(n: f64) -> Option<u16> {
+    if n.fract() == 0.0 && (0.0..=u16::MAX as f64).contains(&n) {
+        Some(n as u16)
+    } else {
+        None
+    }
+}
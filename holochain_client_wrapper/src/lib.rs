@@ -1,20 +1,85 @@
+use std::{collections::HashMap, rc::Rc};
+
 use js_sys::{Array, Function, JsString, Number, Object, Promise, Reflect, Uint8Array};
 use wasm_bindgen::{prelude::*, JsCast};
 use wasm_bindgen_futures::JsFuture;
 
 use macros::generate_call;
+use serde::de::DeserializeOwned;
+
+mod app_client;
+mod app_interface;
+mod broadcast;
+mod call;
+mod capability;
+mod cell_info;
+mod clone_cell;
+mod connection;
+mod error;
+mod install;
+pub mod keystore;
+mod launcher;
+mod membrane_proof;
+mod provision;
+pub mod queue;
+mod reconnect;
+pub mod record;
+mod retry;
+mod signal;
+mod signing;
+
+pub use app_client::{AppClient, AppClientError};
+pub use app_interface::AppInterfaceError;
+use app_interface::{attached_port, listed_ports};
+pub use broadcast::Subscription;
+use call::with_deadline;
+pub use call::{CallError, CallOptions, CancelHandle, Cancellation};
+pub use capability::{CapAccess, CapSecret, ZomeCallCapGrant};
+pub use cell_info::{CellInfo, DnaModifiers, ProvisionedCell, RoleName, StemCell};
+pub use clone_cell::{CloneCellId, CloneId, ClonedCell, DnaModifiersOpt, InstalledCell};
+use connection::Connection;
+pub use error::{ConductorError, ZomeCallError};
+pub use install::{DnaSource, InstallAppBuilder, InstallAppError, InstallRole};
+pub use launcher::{connect_from_env, LauncherEnv, LauncherEnvError};
+pub use membrane_proof::{InviteCode, InviterKey, MembraneProof};
+pub use provision::{
+    AppChange, AppSpec, DnaSpec, ProvisionError, ProvisionProgress, ProvisionStep, ProvisionedApp,
+};
+pub use reconnect::{
+    Backoff, ConnectionState, ReconnectingAdminWebsocket, ReconnectingAppWebsocket,
+    ReconnectingWebsocket,
+};
+pub use retry::{ErrorKind, RetryPolicy};
+use signal::SignalHub;
+pub use signal::{AppSignal, MalformedSignal, Signal, SystemSignal, TypedSignals};
+pub use signing::{
+    encode_payload, SigningCredentials, ZomeCallSigner, ZomeCallUnsigned, SIGNING_GRANT_TAG,
+};
 
 ////////////////////////////////////////////////////////////////////////////////
 // wasm_bindgen key bindings
 ////////////////////////////////////////////////////////////////////////////////
 
-#[wasm_bindgen(module = "/src/holochain_client_wrapper.js")]
+// the `js-stub` feature swaps the `holochain-client-js` bundle for a scriptable fake, so that the
+// test suite can run under `wasm-bindgen-test` without a conductor.
+#[cfg_attr(
+    not(feature = "js-stub"),
+    wasm_bindgen(module = "/src/holochain_client_wrapper.js")
+)]
+#[cfg_attr(
+    feature = "js-stub",
+    wasm_bindgen(module = "/tests/js/holochain_client_wrapper_stub.js")
+)]
 extern "C" {
     #[wasm_bindgen(catch, js_namespace = AdminWebsocket, js_name="connect")]
     async fn connect_admin_ws_js(url: String, timeout: Option<u32>) -> Result<JsValue, JsValue>;
 
     #[wasm_bindgen(catch, js_namespace = AppWebsocket, js_name="connect")]
-    async fn connect_app_ws_js(url: String, timeout: Option<u32>) -> Result<JsValue, JsValue>;
+    async fn connect_app_ws_js(
+        url: String,
+        timeout: Option<u32>,
+        signal_cb: &Function,
+    ) -> Result<JsValue, JsValue>;
 }
 
 ////////////////////////////////////////////////////////////////////////////////
@@ -31,42 +96,83 @@ pub type CellId = (DnaHash, AgentPk);
 
 pub type CellIdVec = Vec<CellId>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HashRoleProof {
     pub hash: DnaHash,
     pub role_id: String,
-    pub membrane_proof: Option<String>,
+    pub membrane_proof: Option<MembraneProof>,
 }
 
 pub type CellIdRoleIdVec = Vec<CellIdRoleId>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct AppInfo {
     pub installed_app_id: String,
-    pub cell_data: CellIdRoleIdVec,
+    /// each role's cells: its provisioned cell (or stem), then any clones. see `cell_data` for the
+    /// flat list conductors used to report.
+    pub cell_info: HashMap<RoleName, Vec<CellInfo>>,
     pub status: String,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct CellIdRoleId {
     pub cell_id: CellId,
     pub role_id: String,
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct EntryHashRaw(JsValue);
 
 #[derive(Clone, Debug)]
 pub struct HeaderHashRaw(JsValue);
 
+// hashes compare by their bytes: two `Uint8Array`s holding the same hash are distinct JS objects.
+
+impl PartialEq for DnaHash {
+    fn eq(&self, other: &Self) -> bool {
+        js_bytes_eq(&self.0, &other.0)
+    }
+}
+
+impl PartialEq for AgentPk {
+    fn eq(&self, other: &Self) -> bool {
+        js_bytes_eq(&self.0, &other.0)
+    }
+}
+
+impl PartialEq for EntryHashRaw {
+    fn eq(&self, other: &Self) -> bool {
+        js_bytes_eq(&self.0, &other.0)
+    }
+}
+
+impl PartialEq for HeaderHashRaw {
+    fn eq(&self, other: &Self) -> bool {
+        js_bytes_eq(&self.0, &other.0)
+    }
+}
+
 pub type EntryHeaderHashPairRaw = (EntryHashRaw, HeaderHashRaw);
 
 pub type ActiveApps = Vec<String>;
 
+/// which apps `ListApps` lists, by status.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AppStatusFilter {
+    Enabled,
+    Disabled,
+    Running,
+    Stopped,
+    Paused,
+}
+
 ////////////////////////////////////////////////////////////////////////////////
 // SerializeToJsObj trait
 ////////////////////////////////////////////////////////////////////////////////
 
+/// types which implement both this & `DeserializeFromJsObj` must round-trip, i.e.
+/// `T::deserialize_from_js_obj(x.serialize_to_js_obj()) == x`. this is checked by
+/// `tests/round_trip.rs`.
 pub trait SerializeToJsObj {
     fn serialize_to_js_obj(self) -> JsValue;
 }
@@ -83,12 +189,32 @@ impl SerializeToJsObj for u16 {
     }
 }
 
+// `JsValue::from(i64)` produces a `BigInt`, which the conductor's msgpack encoder doesn't expect.
+impl SerializeToJsObj for i64 {
+    fn serialize_to_js_obj(self) -> JsValue {
+        JsValue::from_f64(self as f64)
+    }
+}
+
 impl SerializeToJsObj for String {
     fn serialize_to_js_obj(self) -> JsValue {
         self.into()
     }
 }
 
+impl SerializeToJsObj for AppStatusFilter {
+    fn serialize_to_js_obj(self) -> JsValue {
+        match self {
+            AppStatusFilter::Enabled => "enabled",
+            AppStatusFilter::Disabled => "disabled",
+            AppStatusFilter::Running => "running",
+            AppStatusFilter::Stopped => "stopped",
+            AppStatusFilter::Paused => "paused",
+        }
+        .into()
+    }
+}
+
 impl<T: SerializeToJsObj> SerializeToJsObj for Option<T> {
     fn serialize_to_js_obj(self) -> JsValue {
         match self {
@@ -122,7 +248,7 @@ impl<A: SerializeToJsObj, B: SerializeToJsObj, C: SerializeToJsObj> SerializeToJ
 impl<T: SerializeToJsObj> SerializeToJsObj for Vec<T> {
     fn serialize_to_js_obj(self) -> JsValue {
         let val = Array::new();
-        for e in self.into_iter().rev() {
+        for e in self.into_iter() {
             let _ = val.push(&e.serialize_to_js_obj());
         }
         val.dyn_into().expect("Array conversion to succeed")
@@ -150,6 +276,13 @@ impl SerializeToJsObj for EntryHashRaw {
     }
 }
 
+impl SerializeToJsObj for HeaderHashRaw {
+    fn serialize_to_js_obj(self) -> JsValue {
+        let Self(val) = self;
+        val
+    }
+}
+
 impl SerializeToJsObj for HashRoleProof {
     fn serialize_to_js_obj(self) -> JsValue {
         let ret = move || -> Result<JsValue, JsValue> {
@@ -191,14 +324,17 @@ impl SerializeToJsObj for AppInfo {
             )?);
             assert!(Reflect::set(
                 &val,
-                &JsValue::from_str("cell_data"),
-                &self.cell_data.serialize_to_js_obj(),
+                &JsValue::from_str("cell_info"),
+                &self.cell_info.serialize_to_js_obj(),
             )?);
+            // the conductor represents the status as a single-key object, e.g. `{ running: null }`.
+            let status: JsValue = Object::new().dyn_into()?;
             assert!(Reflect::set(
-                &val,
-                &JsValue::from_str("status"),
+                &status,
                 &self.status.serialize_to_js_obj(),
+                &JsValue::NULL,
             )?);
+            assert!(Reflect::set(&val, &JsValue::from_str("status"), &status)?);
             Ok(val)
         };
         ret().expect("operations to succeed")
@@ -252,6 +388,32 @@ impl<A: DeserializeFromJsObj, B: DeserializeFromJsObj> DeserializeFromJsObj for
     }
 }
 
+impl<A: DeserializeFromJsObj, B: DeserializeFromJsObj, C: DeserializeFromJsObj> DeserializeFromJsObj
+    for (A, B, C)
+{
+    fn deserialize_from_js_obj(v: JsValue) -> Self {
+        let arr: Array = v.dyn_into().expect("Array conversion to succeed");
+        let a = arr.at(0);
+        let b = arr.at(1);
+        let c = arr.at(2);
+        (
+            A::deserialize_from_js_obj(a),
+            B::deserialize_from_js_obj(b),
+            C::deserialize_from_js_obj(c),
+        )
+    }
+}
+
+impl<T: DeserializeFromJsObj> DeserializeFromJsObj for Option<T> {
+    fn deserialize_from_js_obj(v: JsValue) -> Self {
+        if v.is_null() || v.is_undefined() {
+            None
+        } else {
+            Some(T::deserialize_from_js_obj(v))
+        }
+    }
+}
+
 impl<T: DeserializeFromJsObj> DeserializeFromJsObj for Vec<T> {
     fn deserialize_from_js_obj(v: JsValue) -> Self {
         let arr: Array = v.dyn_into().expect("Array conversion to succeed");
@@ -265,6 +427,19 @@ impl<T: DeserializeFromJsObj> DeserializeFromJsObj for Vec<T> {
     }
 }
 
+impl DeserializeFromJsObj for JsValue {
+    fn deserialize_from_js_obj(v: JsValue) -> Self {
+        v
+    }
+}
+
+impl DeserializeFromJsObj for u16 {
+    fn deserialize_from_js_obj(v: JsValue) -> Self {
+        let number: Number = v.dyn_into().expect("Number conversion to succeed");
+        number.value_of() as u16
+    }
+}
+
 impl DeserializeFromJsObj for i64 {
     fn deserialize_from_js_obj(v: JsValue) -> Self {
         let number: Number = v.dyn_into().expect("String conversion to succeed");
@@ -291,15 +466,43 @@ impl DeserializeFromJsObj for DnaHash {
     }
 }
 
+impl DeserializeFromJsObj for HashRoleProof {
+    fn deserialize_from_js_obj(v: JsValue) -> Self {
+        let hash = DnaHash::deserialize_from_js_obj(
+            Reflect::get(&v, &JsValue::from_str("hash")).expect("object field get to succeed"),
+        );
+        let role_id = String::deserialize_from_js_obj(
+            Reflect::get(&v, &JsValue::from_str("role_id")).expect("object field get to succeed"),
+        );
+        let membrane_proof = Option::<MembraneProof>::deserialize_from_js_obj(
+            Reflect::get(&v, &JsValue::from_str("membrane_proof"))
+                .expect("object field get to succeed"),
+        );
+        Self {
+            hash,
+            role_id,
+            membrane_proof,
+        }
+    }
+}
+
 impl DeserializeFromJsObj for AppInfo {
     fn deserialize_from_js_obj(v: JsValue) -> Self {
         let installed_app_id = String::deserialize_from_js_obj(
             Reflect::get(&v, &JsValue::from_str("installed_app_id"))
                 .expect("object field get to succeed"),
         );
-        let cell_data = CellIdRoleIdVec::deserialize_from_js_obj(
-            Reflect::get(&v, &JsValue::from_str("cell_data")).expect("object field get to succeed"),
-        );
+        // older conductors only report a flat `cell_data` list.
+        let cell_info =
+            Reflect::get(&v, &JsValue::from_str("cell_info")).expect("object field get to succeed");
+        let cell_info = if cell_info.is_object() {
+            HashMap::<RoleName, Vec<CellInfo>>::deserialize_from_js_obj(cell_info)
+        } else {
+            AppInfo::cell_info_from_cell_data(CellIdRoleIdVec::deserialize_from_js_obj(
+                Reflect::get(&v, &JsValue::from_str("cell_data"))
+                    .expect("object field get to succeed"),
+            ))
+        };
         let status = {
             let status_obj: Object = Reflect::get(&v, &JsValue::from_str("status"))
                 .expect("object field get to succeed")
@@ -309,7 +512,7 @@ impl DeserializeFromJsObj for AppInfo {
         };
         Self {
             installed_app_id,
-            cell_data,
+            cell_info,
             status,
         }
     }
@@ -343,6 +546,14 @@ impl DeserializeFromJsObj for HeaderHashRaw {
 // helpers
 ////////////////////////////////////////////////////////////////////////////////
 
+/// byte-wise equality for `Uint8Array`s, falling back to JS `===` for anything else.
+fn js_bytes_eq(a: &JsValue, b: &JsValue) -> bool {
+    match (a.dyn_ref::<Uint8Array>(), b.dyn_ref::<Uint8Array>()) {
+        (Some(a), Some(b)) => a.to_vec() == b.to_vec(),
+        _ => a == b,
+    }
+}
+
 pub fn agent_pk_to_vec_u8(AgentPk(v): AgentPk) -> Vec<u8> {
     let arr: Uint8Array = v.dyn_into().expect("Uint8Array conversion to succeed");
     arr.to_vec()
@@ -352,26 +563,66 @@ pub fn agent_pk_to_vec_u8(AgentPk(v): AgentPk) -> Vec<u8> {
 // AdminWebsocket
 ////////////////////////////////////////////////////////////////////////////////
 
+/// clones share one connection, which is closed by `close` or when the last clone is dropped.
 #[derive(Clone, Debug)]
 pub struct AdminWebsocket {
     pub js_ws: JsValue,
+    /// the url connected to. `None` for websockets built from a `JsValue`.
+    pub url: Option<String>,
+    /// deadline, in milliseconds, for calls which don't set their own via `CallOptions`.
+    pub call_timeout: Option<u32>,
+    connection: Rc<Connection>,
 }
 
+/// the websocket takes ownership of `val`, closing it once dropped.
 impl From<JsValue> for AdminWebsocket {
     fn from(val: JsValue) -> Self {
-        AdminWebsocket { js_ws: val }
+        AdminWebsocket {
+            js_ws: val.clone(),
+            url: None,
+            call_timeout: None,
+            connection: Connection::new(val),
+        }
     }
 }
 
+/// converting the last clone hands the connection over to JS, without closing it.
 impl From<AdminWebsocket> for JsValue {
     fn from(ws: AdminWebsocket) -> Self {
+        Connection::release(ws.connection);
         ws.js_ws
     }
 }
 
+impl AdminWebsocket {
+    /// closes the connection for every clone. pending & future calls fail with
+    /// `CallError::Closed`.
+    pub async fn close(&self) -> Result<(), CallError> {
+        self.connection.close().await
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.connection.is_closed()
+    }
+
+    /// the same connection, with its `js_ws` swapped for `js_ws` (e.g. a recording wrapper).
+    pub(crate) fn with_js_ws(&self, js_ws: JsValue) -> AdminWebsocket {
+        AdminWebsocket {
+            js_ws,
+            ..self.clone()
+        }
+    }
+}
+
+/// `timeout` bounds the connection attempt and becomes the default deadline for every `call`.
 pub async fn connect_admin_ws(url: String, timeout: Option<u32>) -> Result<AdminWebsocket, String> {
-    match connect_admin_ws_js(url, timeout).await {
-        Ok(js_ws) => Ok(AdminWebsocket { js_ws }),
+    match connect_admin_ws_js(url.clone(), timeout).await {
+        Ok(js_ws) => Ok(AdminWebsocket {
+            js_ws: js_ws.clone(),
+            url: Some(url),
+            call_timeout: timeout,
+            connection: Connection::new(js_ws),
+        }),
         Err(js_err) => Err(format!("{:?}", js_err)),
     }
 }
@@ -389,6 +640,7 @@ pub async fn connect_admin_ws(url: String, timeout: Option<u32>) -> Result<Admin
 )]
 #[derive(Clone, Debug)]
 pub enum AdminWsCmd {
+    /// `port` 0 lets the conductor pick a free port, which is returned.
     AttachAppInterface {
         port: u16,
     },
@@ -417,6 +669,20 @@ pub enum AdminWsCmd {
     ListDnas,
     ListCellIds,
     ListActiveApps,
+    ListAppInterfaces,
+    /// every installed app, or only those with `status_filter`'s status.
+    ListApps {
+        status_filter: Option<AppStatusFilter>,
+    },
+    GrantZomeCallCapability {
+        cell_id: CellId,
+        cap_grant: ZomeCallCapGrant,
+    },
+    /// permanently deletes `role_id`'s archived clone cells.
+    DeleteArchivedCloneCells {
+        app_id: String,
+        role_id: String,
+    },
     // RequestAgentInfo({ cell_id }),
     // AddAgentInfo({ agent_infos }),
 }
@@ -426,7 +692,8 @@ pub enum AdminWsCmd {
 
 #[derive(Clone, Debug)]
 pub enum AdminWsCmdResponse {
-    AttachAppInterface(JsValue),
+    /// the attached port.
+    AttachAppInterface(u16),
     DisableApp(JsValue),
     // DumpState(JsValue),
     EnableApp(JsValue),
@@ -438,13 +705,18 @@ pub enum AdminWsCmdResponse {
     ListDnas(JsValue),
     ListCellIds(Vec<CellId>),
     ListActiveApps(ActiveApps),
+    /// the interfaces' ports.
+    ListAppInterfaces(Vec<u16>),
+    ListApps(Vec<AppInfo>),
+    GrantZomeCallCapability(JsValue),
+    DeleteArchivedCloneCells(JsValue),
     // RequestAgentInfo(JsValue),
     // AddAgentInfo(JsValue),
 }
 
 fn parse_admin_ws_cmd_response(val: JsValue, tag: String) -> AdminWsCmdResponse {
     match tag.as_str() {
-        "AttachAppInterface" => AdminWsCmdResponse::AttachAppInterface(val),
+        "AttachAppInterface" => AdminWsCmdResponse::AttachAppInterface(attached_port(val)),
         "DisableApp" => AdminWsCmdResponse::DisableApp(val),
         // "DumpState" => AdminWsCmdResponse::DumpState(val),
         "EnableApp" => AdminWsCmdResponse::EnableApp(val),
@@ -460,6 +732,10 @@ fn parse_admin_ws_cmd_response(val: JsValue, tag: String) -> AdminWsCmdResponse
         "ListActiveApps" => {
             AdminWsCmdResponse::ListActiveApps(ActiveApps::deserialize_from_js_obj(val))
         }
+        "ListAppInterfaces" => AdminWsCmdResponse::ListAppInterfaces(listed_ports(val)),
+        "ListApps" => AdminWsCmdResponse::ListApps(Vec::<AppInfo>::deserialize_from_js_obj(val)),
+        "GrantZomeCallCapability" => AdminWsCmdResponse::GrantZomeCallCapability(val),
+        "DeleteArchivedCloneCells" => AdminWsCmdResponse::DeleteArchivedCloneCells(val),
         // "RequestAgentInfo" => AdminWsCmdResponse::RequestAgentInfo(val),
         // "AddAgentInfo" => AdminWsCmdResponse::AddAgentInfo(val),
         other => panic!(
@@ -490,26 +766,111 @@ fn parse_admin_ws_cmd_response(val: JsValue, tag: String) -> AdminWsCmdResponse
 // AppWebsocket
 ////////////////////////////////////////////////////////////////////////////////
 
+/// clones share one connection, which is closed by `close` or when the last clone is dropped.
 #[derive(Clone, Debug)]
 pub struct AppWebsocket {
     pub js_ws: JsValue,
+    /// deadline, in milliseconds, for calls which don't set their own via `CallOptions`.
+    pub call_timeout: Option<u32>,
+    signal_hub: SignalHub,
+    connection: Rc<Connection>,
 }
 
+/// the websocket takes ownership of `val`, closing it once dropped.
+///
+/// n.b. a websocket built this way never yields signals, since the signal callback can only be
+/// registered when connecting.
 impl From<JsValue> for AppWebsocket {
     fn from(val: JsValue) -> Self {
-        AppWebsocket { js_ws: val }
+        AppWebsocket {
+            js_ws: val.clone(),
+            call_timeout: None,
+            signal_hub: SignalHub::default(),
+            connection: Connection::new(val),
+        }
+    }
+}
+
+impl AppWebsocket {
+    /// closes the connection for every clone. pending & future calls fail with
+    /// `CallError::Closed`.
+    pub async fn close(&self) -> Result<(), CallError> {
+        self.connection.close().await
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.connection.is_closed()
+    }
+
+    /// a stream of every app signal received from now on. each call returns an independent
+    /// subscription; dropping it unsubscribes.
+    pub fn signals(&self) -> Subscription<AppSignal> {
+        self.signal_hub.app.subscribe()
+    }
+
+    /// like `signals`, but including system signals.
+    pub fn all_signals(&self) -> Subscription<Signal> {
+        self.signal_hub.all.subscribe()
+    }
+
+    /// signals from `zome_name` in `cell_id`, with payloads decoded into `T`. payloads which
+    /// don't decode are reported on `malformed_signals` instead. signals which don't name their
+    /// zome are never delivered here; see `TypedSignals`.
+    pub fn subscribe_typed<T: DeserializeOwned>(
+        &self,
+        cell_id: CellId,
+        zome_name: impl Into<String>,
+    ) -> TypedSignals<T> {
+        TypedSignals::new(
+            self.signal_hub.app.subscribe(),
+            cell_id,
+            zome_name.into(),
+            self.signal_hub.malformed.clone(),
+        )
+    }
+
+    /// signals which were dropped because they couldn't be parsed, or decoded for a typed
+    /// subscription.
+    pub fn malformed_signals(&self) -> Subscription<MalformedSignal> {
+        self.signal_hub.malformed.subscribe()
+    }
+
+    /// the same connection, with its `js_ws` swapped for `js_ws` (e.g. a recording wrapper) but
+    /// still delivering this connection's signals.
+    pub(crate) fn with_js_ws(&self, js_ws: JsValue) -> AppWebsocket {
+        AppWebsocket {
+            js_ws,
+            ..self.clone()
+        }
     }
 }
 
+/// converting the last clone hands the connection over to JS, without closing it.
 impl From<AppWebsocket> for JsValue {
     fn from(ws: AppWebsocket) -> Self {
+        Connection::release(ws.connection);
         ws.js_ws
     }
 }
 
+/// `timeout` bounds the connection attempt and becomes the default deadline for every `call`.
 pub async fn connect_app_ws(url: String, timeout: Option<u32>) -> Result<AppWebsocket, String> {
-    match connect_app_ws_js(url, timeout).await {
-        Ok(js_ws) => Ok(AppWebsocket { js_ws }),
+    connect_app_ws_with_hub(url, timeout, SignalHub::default()).await
+}
+
+/// connects, delivering signals to an existing `signal_hub`'s subscribers.
+pub(crate) async fn connect_app_ws_with_hub(
+    url: String,
+    timeout: Option<u32>,
+    signal_hub: SignalHub,
+) -> Result<AppWebsocket, String> {
+    match connect_app_ws_js(url, timeout, signal_hub.callback().unchecked_ref()).await {
+        Ok(js_ws) => Ok(AppWebsocket {
+            js_ws: js_ws.clone(),
+            call_timeout: timeout,
+            signal_hub,
+            connection: Connection::new(js_ws),
+        }),
         Err(js_err) => Err(format!("{:?}", js_err)),
     }
 }
@@ -526,7 +887,25 @@ pub enum AppWsCmd {
         fn_name: String,
         payload: JsValue,
         provenance: AgentPk,
-        cap: String,
+        /// `None` for calls which need no capability, e.g. the agent's own calls to its cells.
+        cap: Option<CapSecret>,
+    },
+    CreateCloneCell {
+        app_id: String,
+        /// the role whose cell to clone.
+        role_id: String,
+        modifiers: DnaModifiersOpt,
+        membrane_proof: Option<MembraneProof>,
+        name: Option<String>,
+    },
+    /// archives a clone cell, which `EnableCloneCell` can restore.
+    DisableCloneCell {
+        app_id: String,
+        clone_cell_id: CloneCellId,
+    },
+    EnableCloneCell {
+        app_id: String,
+        clone_cell_id: CloneCellId,
     },
 }
 
@@ -534,12 +913,22 @@ pub enum AppWsCmd {
 pub enum AppWsCmdResponse {
     AppInfo(AppInfo),
     CallZome(JsValue),
+    CreateCloneCell(ClonedCell),
+    DisableCloneCell(JsValue),
+    EnableCloneCell(ClonedCell),
 }
 
 fn parse_app_ws_cmd_response(val: JsValue, tag: String) -> AppWsCmdResponse {
     match tag.as_str() {
         "AppInfo" => AppWsCmdResponse::AppInfo(AppInfo::deserialize_from_js_obj(val)),
         "CallZome" => AppWsCmdResponse::CallZome(val),
+        "CreateCloneCell" => {
+            AppWsCmdResponse::CreateCloneCell(ClonedCell::deserialize_from_js_obj(val))
+        }
+        "DisableCloneCell" => AppWsCmdResponse::DisableCloneCell(val),
+        "EnableCloneCell" => {
+            AppWsCmdResponse::EnableCloneCell(ClonedCell::deserialize_from_js_obj(val))
+        }
         other => panic!(
             "parse_app_ws_cmd_response: impossible: received unknown tag: {}",
             other
@@ -551,6 +940,7 @@ fn parse_app_ws_cmd_response(val: JsValue, tag: String) -> AppWsCmdResponse {
 // ZomeCallable
 ////////////////////////////////////////////////////////////////////////////////
 
+#[allow(dead_code)]
 trait ZomeCallable {
     type Input;
     type Output;
@@ -0,0 +1,245 @@
+//! membrane proofs: what an agent presents to join a DNA's network, checked by its
+//! `genesis_self_check` & membrane validation. the conductor treats them as opaque bytes.
+//!
+//! invite codes are the membrane proofs of invite-only DNAs. an inviter (typically the
+//! progenitor, whose public key is one of the DNA's properties) signs the DNA hash & the
+//! invitee's agent key with an ed25519 `InviterKey`, and hands the invitee the resulting code,
+//! e.g. in a link. the invitee's app parses it & installs with it as the membrane proof, which
+//! the DNA's validation checks against the inviter's key.
+
+use std::{fmt, str::FromStr};
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use js_sys::Uint8Array;
+use serde::{Deserialize, Serialize};
+use serde_bytes::ByteBuf;
+use wasm_bindgen::{prelude::*, JsCast};
+use zeroize::Zeroizing;
+
+use crate::{
+    encode_payload, signing::agent_pub_key, AgentPk, DeserializeFromJsObj, DnaHash,
+    SerializeToJsObj,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+// MembraneProof
+////////////////////////////////////////////////////////////////////////////////
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct MembraneProof(Vec<u8>);
+
+impl MembraneProof {
+    /// `payload`, msgpack-encoded as `@msgpack/msgpack` would (see `encode_payload`), e.g. for a
+    /// DNA whose validation decodes its membrane proofs into a struct.
+    pub fn encode(payload: impl SerializeToJsObj) -> Result<Self, String> {
+        encode_payload(&payload.serialize_to_js_obj()).map(MembraneProof)
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for MembraneProof {
+    fn from(bytes: Vec<u8>) -> Self {
+        MembraneProof(bytes)
+    }
+}
+
+impl From<&[u8]> for MembraneProof {
+    fn from(bytes: &[u8]) -> Self {
+        MembraneProof(bytes.to_vec())
+    }
+}
+
+impl From<Uint8Array> for MembraneProof {
+    fn from(bytes: Uint8Array) -> Self {
+        MembraneProof(bytes.to_vec())
+    }
+}
+
+impl From<InviteCode> for MembraneProof {
+    fn from(invite: InviteCode) -> Self {
+        MembraneProof(invite.to_bytes())
+    }
+}
+
+impl fmt::Debug for MembraneProof {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "MembraneProof({} bytes)", self.0.len())
+    }
+}
+
+impl SerializeToJsObj for MembraneProof {
+    fn serialize_to_js_obj(self) -> JsValue {
+        Uint8Array::from(&self.0[..]).into()
+    }
+}
+
+impl DeserializeFromJsObj for MembraneProof {
+    fn deserialize_from_js_obj(v: JsValue) -> Self {
+        let bytes: Uint8Array = v.dyn_into().expect("Uint8Array conversion to succeed");
+        MembraneProof(bytes.to_vec())
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// invite codes
+////////////////////////////////////////////////////////////////////////////////
+
+/// the ed25519 key invites are signed with.
+#[derive(Clone)]
+pub struct InviterKey(SigningKey);
+
+impl InviterKey {
+    /// a fresh key, from the platform's CSPRNG.
+    pub fn generate() -> Self {
+        let mut seed = Zeroizing::new([0; 32]);
+        getrandom::getrandom(&mut *seed).expect("a source of randomness to be available");
+        InviterKey(SigningKey::from_bytes(&seed))
+    }
+
+    /// the key for a 32 byte seed, e.g. one kept by the progenitor's own app.
+    pub fn from_seed(seed: &[u8; 32]) -> Self {
+        InviterKey(SigningKey::from_bytes(seed))
+    }
+
+    /// the public key, as an agent key: the form DNA properties & invite codes carry it in.
+    pub fn public_key(&self) -> AgentPk {
+        let public_key = agent_pub_key(self.0.verifying_key().as_bytes());
+        AgentPk(Uint8Array::from(&public_key[..]).into())
+    }
+
+    /// an invite for `invitee` to join `dna_hash`'s network.
+    pub fn invite(&self, dna_hash: &DnaHash, invitee: &AgentPk) -> InviteCode {
+        let dna_hash = bytes_of(&dna_hash.0);
+        let invitee = bytes_of(&invitee.0);
+        let signature = self.0.sign(&signed_data(&dna_hash, &invitee)).to_bytes();
+        InviteCode {
+            dna_hash,
+            invitee,
+            inviter: bytes_of(&self.public_key().0),
+            signature,
+        }
+    }
+}
+
+/// deliberately doesn't print the key.
+impl fmt::Debug for InviterKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InviterKey")
+            .field("public_key", &self.public_key())
+            .finish_non_exhaustive()
+    }
+}
+
+/// an invite to join a DNA's network. as a membrane proof, it's the msgpack map
+/// `{ dna_hash, invitee, inviter, signature }` (all binary), where `signature` is the inviter's
+/// ed25519 signature of the DNA hash's bytes followed by the invitee's. as text (`to_string`,
+/// `parse`), it's that map in url-safe base64.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InviteCode {
+    dna_hash: Vec<u8>,
+    invitee: Vec<u8>,
+    inviter: Vec<u8>,
+    signature: [u8; 64],
+}
+
+#[derive(Serialize, Deserialize)]
+struct InviteCodeWire {
+    dna_hash: ByteBuf,
+    invitee: ByteBuf,
+    inviter: ByteBuf,
+    signature: ByteBuf,
+}
+
+impl InviteCode {
+    pub fn dna_hash(&self) -> DnaHash {
+        DnaHash(Uint8Array::from(&self.dna_hash[..]).into())
+    }
+
+    pub fn invitee(&self) -> AgentPk {
+        AgentPk(Uint8Array::from(&self.invitee[..]).into())
+    }
+
+    pub fn inviter(&self) -> AgentPk {
+        AgentPk(Uint8Array::from(&self.inviter[..]).into())
+    }
+
+    /// whether the signature is the inviter's. the DNA decides which inviters it trusts.
+    pub fn verify(&self) -> bool {
+        // an agent key is a 3 byte prefix, the 32 byte public key & a 4 byte location.
+        let public_key = match self.inviter.get(3..35).map(<[u8; 32]>::try_from) {
+            Some(Ok(public_key)) => public_key,
+            _ => return false,
+        };
+        let verifying_key = match VerifyingKey::from_bytes(&public_key) {
+            Ok(verifying_key) => verifying_key,
+            Err(_) => return false,
+        };
+        let signature = Signature::from_bytes(&self.signature);
+        verifying_key
+            .verify(&signed_data(&self.dna_hash, &self.invitee), &signature)
+            .is_ok()
+    }
+
+    /// whether this is an invite for `agent` to join `dna_hash`, validly signed by `inviter`.
+    /// `verify` alone only shows that whoever's key the code carries signed it; anyone can sign
+    /// their own invites, so the inviter must be one the DNA trusts, e.g. its progenitor.
+    pub fn admits(&self, dna_hash: &DnaHash, agent: &AgentPk, inviter: &AgentPk) -> bool {
+        self.dna_hash == bytes_of(&dna_hash.0)
+            && self.invitee == bytes_of(&agent.0)
+            && self.inviter == bytes_of(&inviter.0)
+            && self.verify()
+    }
+
+    /// the membrane proof encoding; see `From<InviteCode> for MembraneProof`.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        rmp_serde::to_vec_named(&InviteCodeWire {
+            dna_hash: ByteBuf::from(self.dna_hash.clone()),
+            invitee: ByteBuf::from(self.invitee.clone()),
+            inviter: ByteBuf::from(self.inviter.clone()),
+            signature: ByteBuf::from(self.signature.to_vec()),
+        })
+        .expect("msgpack encoding to succeed")
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        let wire: InviteCodeWire =
+            rmp_serde::from_slice(bytes).map_err(|_| "undecodable invite code".to_string())?;
+        let signature = <[u8; 64]>::try_from(wire.signature.as_slice())
+            .map_err(|_| "invite code signature isn't 64 bytes".to_string())?;
+        Ok(InviteCode {
+            dna_hash: wire.dna_hash.into_vec(),
+            invitee: wire.invitee.into_vec(),
+            inviter: wire.inviter.into_vec(),
+            signature,
+        })
+    }
+}
+
+impl fmt::Display for InviteCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", URL_SAFE_NO_PAD.encode(self.to_bytes()))
+    }
+}
+
+impl FromStr for InviteCode {
+    type Err = String;
+
+    fn from_str(code: &str) -> Result<Self, String> {
+        let bytes = URL_SAFE_NO_PAD
+            .decode(code.trim())
+            .map_err(|_| "invite code isn't base64".to_string())?;
+        InviteCode::from_bytes(&bytes)
+    }
+}
+
+fn bytes_of(hash: &JsValue) -> Vec<u8> {
+    Uint8Array::new(hash).to_vec()
+}
+
+fn signed_data(dna_hash: &[u8], invitee: &[u8]) -> Vec<u8> {
+    [dna_hash, invitee].concat()
+}
@@ -0,0 +1,381 @@
+//! setting up a new app in one go: `AdminWebsocket::provision_app`.
+//!
+//! provisioning runs `GenerateAgentPubKey` → `RegisterDna` (per DNA) → `InstallApp` → `EnableApp`
+//! → `AttachAppInterface`, reporting each step as it starts & completes. should a step fail, the
+//! app is uninstalled again if it got that far. generated agent keys & registered DNAs can't be
+//! removed through the admin API, so they're left behind; both are harmless on their own.
+//!
+//! `ensure_app_installed` & `ensure_app_enabled` are for setup which runs on every start: they
+//! check the conductor's state first and only install or enable what's missing.
+
+use std::fmt;
+
+use crate::{
+    AdminWebsocket, AdminWsCmd, AdminWsCmdResponse, AgentPk, AppInfo, CallError, DnaHash,
+    HashRoleProof, MembraneProof,
+};
+
+/// a DNA to register & install under `role_id`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DnaSpec {
+    pub role_id: String,
+    /// the DNA file's path, on the conductor's filesystem.
+    pub path: String,
+    pub uid: Option<String>,
+    pub properties: Option<String>,
+    pub membrane_proof: Option<MembraneProof>,
+}
+
+impl DnaSpec {
+    pub fn new(role_id: impl Into<String>, path: impl Into<String>) -> Self {
+        DnaSpec {
+            role_id: role_id.into(),
+            path: path.into(),
+            uid: None,
+            properties: None,
+            membrane_proof: None,
+        }
+    }
+}
+
+/// an app to provision.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AppSpec {
+    pub installed_app_id: String,
+    pub dnas: Vec<DnaSpec>,
+    /// the agent to install the app for. `None` generates a new one.
+    pub agent_key: Option<AgentPk>,
+    /// an app interface port to attach once the app is enabled, or 0 for one the conductor picks.
+    /// `None` attaches nothing.
+    pub app_port: Option<u16>,
+}
+
+impl AppSpec {
+    pub fn new(installed_app_id: impl Into<String>, dnas: Vec<DnaSpec>) -> Self {
+        AppSpec {
+            installed_app_id: installed_app_id.into(),
+            dnas,
+            agent_key: None,
+            app_port: None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProvisionStep {
+    GenerateAgentPubKey,
+    RegisterDna {
+        role_id: String,
+    },
+    InstallApp,
+    EnableApp,
+    AttachAppInterface {
+        port: u16,
+    },
+    /// undoing `InstallApp` (and `EnableApp`), after a later step failed.
+    UninstallApp,
+    /// checking which apps are installed, for `ensure_app_installed`.
+    ListApps,
+}
+
+impl fmt::Display for ProvisionStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProvisionStep::GenerateAgentPubKey => write!(f, "generating an agent key"),
+            ProvisionStep::RegisterDna { role_id } => write!(f, "registering {}'s DNA", role_id),
+            ProvisionStep::InstallApp => write!(f, "installing the app"),
+            ProvisionStep::EnableApp => write!(f, "enabling the app"),
+            ProvisionStep::AttachAppInterface { port } => {
+                write!(f, "attaching an app interface on port {}", port)
+            }
+            ProvisionStep::UninstallApp => write!(f, "uninstalling the app"),
+            ProvisionStep::ListApps => write!(f, "listing the installed apps"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ProvisionProgress {
+    Started(ProvisionStep),
+    Completed(ProvisionStep),
+    Failed(ProvisionStep),
+}
+
+/// a provisioned app.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProvisionedApp {
+    pub installed_app_id: String,
+    pub agent_key: AgentPk,
+    /// each role's registered DNA, in `AppSpec.dnas` order.
+    pub dnas: Vec<(String, DnaHash)>,
+    /// the attached app interface's port.
+    pub app_port: Option<u16>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProvisionError {
+    /// the step which failed.
+    pub step: ProvisionStep,
+    pub error: CallError,
+    /// why undoing the completed steps failed, if it did. the app may still be installed.
+    pub rollback_error: Option<CallError>,
+}
+
+impl fmt::Display for ProvisionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} failed: {}", self.step, self.error)?;
+        if let Some(err) = &self.rollback_error {
+            write!(f, " (and uninstalling the app failed: {})", err)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ProvisionError {}
+
+impl AdminWebsocket {
+    /// provisions `spec`, calling `on_progress` as each step starts, completes or fails. on
+    /// failure, the app is uninstalled if it had been installed.
+    pub async fn provision_app(
+        &self,
+        spec: AppSpec,
+        mut on_progress: impl FnMut(ProvisionProgress),
+    ) -> Result<ProvisionedApp, ProvisionError> {
+        let mut installed = false;
+        let res = self
+            .provision_steps(&spec, &mut installed, &mut on_progress)
+            .await;
+        let (step, error) = match res {
+            Ok(app) => return Ok(app),
+            Err(failure) => failure,
+        };
+        on_progress(ProvisionProgress::Failed(step.clone()));
+        let mut rollback_error = None;
+        if installed {
+            let uninstall = AdminWsCmd::UninstallApp {
+                installed_app_id: spec.installed_app_id.clone(),
+            };
+            if let Err((step, err)) = run_step(
+                self,
+                uninstall,
+                ProvisionStep::UninstallApp,
+                &mut on_progress,
+            )
+            .await
+            {
+                on_progress(ProvisionProgress::Failed(step));
+                rollback_error = Some(err);
+            }
+        }
+        Err(ProvisionError {
+            step,
+            error,
+            rollback_error,
+        })
+    }
+
+    async fn provision_steps(
+        &self,
+        spec: &AppSpec,
+        installed: &mut bool,
+        on_progress: &mut impl FnMut(ProvisionProgress),
+    ) -> Result<ProvisionedApp, (ProvisionStep, CallError)> {
+        let app = self.install_steps(spec, on_progress).await?;
+        *installed = true;
+
+        let enable = AdminWsCmd::EnableApp {
+            installed_app_id: spec.installed_app_id.clone(),
+        };
+        run_step(self, enable, ProvisionStep::EnableApp, on_progress).await?;
+
+        let app_port = match spec.app_port {
+            Some(port) => {
+                let attach = AdminWsCmd::AttachAppInterface { port };
+                let step = ProvisionStep::AttachAppInterface { port };
+                match run_step(self, attach, step, on_progress).await? {
+                    AdminWsCmdResponse::AttachAppInterface(port) => Some(port),
+                    other => panic!("provision_app: impossible: received {:?}", other),
+                }
+            }
+            None => None,
+        };
+
+        Ok(ProvisionedApp { app_port, ..app })
+    }
+
+    /// generates the agent key, registers the DNAs & installs the app.
+    async fn install_steps(
+        &self,
+        spec: &AppSpec,
+        on_progress: &mut impl FnMut(ProvisionProgress),
+    ) -> Result<ProvisionedApp, (ProvisionStep, CallError)> {
+        let agent_key = match &spec.agent_key {
+            Some(agent_key) => agent_key.clone(),
+            None => match run_step(
+                self,
+                AdminWsCmd::GenerateAgentPubKey,
+                ProvisionStep::GenerateAgentPubKey,
+                on_progress,
+            )
+            .await?
+            {
+                AdminWsCmdResponse::GenerateAgentPubKey(agent_key) => agent_key,
+                other => panic!("provision_app: impossible: received {:?}", other),
+            },
+        };
+
+        let mut dnas = Vec::new();
+        for dna in &spec.dnas {
+            let register = AdminWsCmd::RegisterDna {
+                path: dna.path.clone(),
+                uid: dna.uid.clone(),
+                properties: dna.properties.clone(),
+            };
+            let step = ProvisionStep::RegisterDna {
+                role_id: dna.role_id.clone(),
+            };
+            match run_step(self, register, step, on_progress).await? {
+                AdminWsCmdResponse::RegisterDna(hash) => dnas.push((dna.role_id.clone(), hash)),
+                other => panic!("provision_app: impossible: received {:?}", other),
+            }
+        }
+
+        let install = AdminWsCmd::InstallApp {
+            installed_app_id: spec.installed_app_id.clone(),
+            agent_key: agent_key.clone(),
+            dnas: spec
+                .dnas
+                .iter()
+                .zip(&dnas)
+                .map(|(dna, (_, hash))| HashRoleProof {
+                    hash: hash.clone(),
+                    role_id: dna.role_id.clone(),
+                    membrane_proof: dna.membrane_proof.clone(),
+                })
+                .collect(),
+        };
+        run_step(self, install, ProvisionStep::InstallApp, on_progress).await?;
+
+        Ok(ProvisionedApp {
+            installed_app_id: spec.installed_app_id.clone(),
+            agent_key,
+            dnas,
+            app_port: None,
+        })
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// idempotent setup
+////////////////////////////////////////////////////////////////////////////////
+
+/// what `ensure_app_installed` / `ensure_app_enabled` had to do.
+#[derive(Clone, Debug, PartialEq)]
+pub enum AppChange {
+    /// the app was already as asked for.
+    Unchanged,
+    Installed(ProvisionedApp),
+    Enabled,
+}
+
+impl AdminWebsocket {
+    /// installs `spec` unless an app with its `installed_app_id` is already installed, whatever
+    /// its status. an installed app isn't compared against `spec`, and the app isn't enabled &
+    /// `spec.app_port` isn't attached; see `ensure_app_enabled` & `provision_app`.
+    pub async fn ensure_app_installed(&self, spec: AppSpec) -> Result<AppChange, ProvisionError> {
+        let installed = self
+            .installed_apps()
+            .await
+            .map_err(|error| ProvisionError {
+                step: ProvisionStep::ListApps,
+                error,
+                rollback_error: None,
+            })?;
+        if installed
+            .iter()
+            .any(|app| app.installed_app_id == spec.installed_app_id)
+        {
+            return Ok(AppChange::Unchanged);
+        }
+        // nothing to undo: installing is the last step.
+        match self.install_steps(&spec, &mut |_| {}).await {
+            Ok(app) => Ok(AppChange::Installed(app)),
+            Err((step, error)) => Err(ProvisionError {
+                step,
+                error,
+                rollback_error: None,
+            }),
+        }
+    }
+
+    /// enables `installed_app_id` unless it's active with all of its cells running. an active
+    /// app whose cells aren't all running (e.g. one paused when the conductor restarted) is
+    /// enabled again, which restarts them.
+    pub async fn ensure_app_enabled(
+        &self,
+        installed_app_id: impl Into<String>,
+    ) -> Result<AppChange, CallError> {
+        let installed_app_id = installed_app_id.into();
+        if self.app_is_running(&installed_app_id).await? {
+            return Ok(AppChange::Unchanged);
+        }
+        self.call(AdminWsCmd::EnableApp { installed_app_id })
+            .await?;
+        Ok(AppChange::Enabled)
+    }
+
+    pub(crate) async fn installed_apps(&self) -> Result<Vec<AppInfo>, CallError> {
+        match self
+            .call(AdminWsCmd::ListApps {
+                status_filter: None,
+            })
+            .await?
+        {
+            AdminWsCmdResponse::ListApps(apps) => Ok(apps),
+            other => panic!("installed_apps: impossible: received {:?}", other),
+        }
+    }
+
+    async fn app_is_running(&self, installed_app_id: &str) -> Result<bool, CallError> {
+        let active = match self.call(AdminWsCmd::ListActiveApps).await? {
+            AdminWsCmdResponse::ListActiveApps(active) => active,
+            other => panic!("app_is_running: impossible: received {:?}", other),
+        };
+        if !active.iter().any(|id| id == installed_app_id) {
+            return Ok(false);
+        }
+        let app = self
+            .installed_apps()
+            .await?
+            .into_iter()
+            .find(|app| app.installed_app_id == installed_app_id);
+        let app = match app {
+            Some(app) => app,
+            None => return Ok(false),
+        };
+        let running = match self.call(AdminWsCmd::ListCellIds).await? {
+            AdminWsCmdResponse::ListCellIds(cell_ids) => cell_ids,
+            other => panic!("app_is_running: impossible: received {:?}", other),
+        };
+        Ok(app
+            .provisioned_cells()
+            .iter()
+            .all(|cell| running.contains(&cell.cell_id)))
+    }
+}
+
+async fn run_step(
+    ws: &AdminWebsocket,
+    cmd: AdminWsCmd,
+    step: ProvisionStep,
+    on_progress: &mut impl FnMut(ProvisionProgress),
+) -> Result<AdminWsCmdResponse, (ProvisionStep, CallError)> {
+    on_progress(ProvisionProgress::Started(step.clone()));
+    match ws.call(cmd).await {
+        Ok(resp) => {
+            on_progress(ProvisionProgress::Completed(step));
+            Ok(resp)
+        }
+        Err(err) => Err((step, err)),
+    }
+}
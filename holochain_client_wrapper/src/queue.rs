@@ -0,0 +1,354 @@
+//! an offline queue for zome calls.
+//!
+//! calls `enqueue`d on a `ZomeCallQueue` are persisted through a `QueueStorage` and sent in order
+//! whenever its `ReconnectingAppWebsocket` is connected. a call whose connection drops mid-flight
+//! is sent again after reconnecting, so only idempotent calls should be queued; anything else
+//! should go through `call`, which is sent once, as usual.
+//!
+//! calls are stored as JSON, so payloads should be plain objects, arrays, strings, numbers &
+//! `Uint8Array`s. anything else is flattened to its own enumerable fields, e.g. a `Map` is stored
+//! (and later sent) as `{}` & a class instance loses its prototype, and a payload containing a
+//! `BigInt` can't be stored at all, so `enqueue` rejects it.
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::VecDeque,
+    fmt,
+    future::Future,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use futures::{
+    channel::{
+        mpsc::{unbounded, UnboundedReceiver, UnboundedSender},
+        oneshot,
+    },
+    StreamExt,
+};
+use js_sys::{Array, Function, Object, Reflect, Uint8Array, JSON};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::spawn_local;
+
+use crate::{
+    record::{from_plain, to_plain},
+    AgentPk, AppWsCmd, AppWsCmdResponse, CallError, CapSecret, CellId, ConnectionState,
+    DeserializeFromJsObj, ReconnectingAppWebsocket, SerializeToJsObj,
+};
+
+////////////////////////////////////////////////////////////////////////////////
+// QueueStorage
+////////////////////////////////////////////////////////////////////////////////
+
+/// where queued calls are kept, so that they survive a page reload. each call is stored as an
+/// opaque JSON string.
+pub trait QueueStorage {
+    /// the queue as last saved, oldest call first.
+    fn load(&self) -> Vec<String>;
+    fn save(&self, calls: &[String]);
+}
+
+/// keeps the queue in memory only. clones share the same queue.
+#[derive(Clone, Debug, Default)]
+pub struct MemoryStorage(Rc<RefCell<Vec<String>>>);
+
+impl QueueStorage for MemoryStorage {
+    fn load(&self) -> Vec<String> {
+        self.0.borrow().clone()
+    }
+
+    fn save(&self, calls: &[String]) {
+        *self.0.borrow_mut() = calls.to_vec();
+    }
+}
+
+/// keeps the queue in the browser's `localStorage`, as a JSON array under `key`. does nothing
+/// where `localStorage` isn't available.
+#[derive(Clone, Debug)]
+pub struct LocalStorage {
+    pub key: String,
+}
+
+impl LocalStorage {
+    fn method(name: &str) -> Option<(JsValue, Function)> {
+        let storage = Reflect::get(&js_sys::global(), &JsValue::from_str("localStorage")).ok()?;
+        let method = Reflect::get(&storage, &JsValue::from_str(name))
+            .ok()?
+            .dyn_into()
+            .ok()?;
+        Some((storage, method))
+    }
+}
+
+impl QueueStorage for LocalStorage {
+    fn load(&self) -> Vec<String> {
+        let stored = Self::method("getItem")
+            .and_then(|(storage, get_item)| {
+                get_item.call1(&storage, &self.key.as_str().into()).ok()
+            })
+            .and_then(|item| item.as_string())
+            .and_then(|json| JSON::parse(&json).ok());
+        match stored {
+            Some(arr) if Array::is_array(&arr) => Array::from(&arr)
+                .iter()
+                .filter_map(|call| call.as_string())
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn save(&self, calls: &[String]) {
+        let arr: Array = calls.iter().map(|call| JsValue::from_str(call)).collect();
+        if let (Some((storage, set_item)), Ok(json)) =
+            (Self::method("setItem"), JSON::stringify(&arr))
+        {
+            let _ = set_item.call2(&storage, &self.key.as_str().into(), &json);
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// ZomeCallQueue
+////////////////////////////////////////////////////////////////////////////////
+
+type Outcome = Result<AppWsCmdResponse, CallError>;
+
+/// the eventual outcome of a queued call. resolves to `CallError::Closed` if the websocket is
+/// closed first; the call itself stays in storage, to be sent next time.
+#[derive(Debug)]
+pub struct QueuedCall(oneshot::Receiver<Outcome>);
+
+impl Future for QueuedCall {
+    type Output = Outcome;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Outcome> {
+        Pin::new(&mut self.0)
+            .poll(cx)
+            .map(|res| res.unwrap_or(Err(CallError::Closed)))
+    }
+}
+
+struct Entry {
+    cmd: AppWsCmd,
+    encoded: String,
+    outcome: Option<oneshot::Sender<Outcome>>,
+}
+
+struct QueueState {
+    entries: RefCell<VecDeque<Entry>>,
+    storage: Box<dyn QueueStorage>,
+    /// receivers for the calls loaded from storage, until claimed by `restored`.
+    restored: RefCell<Vec<QueuedCall>>,
+    pumping: Cell<bool>,
+}
+
+impl QueueState {
+    fn persist(&self) {
+        let encoded: Vec<String> = self
+            .entries
+            .borrow()
+            .iter()
+            .map(|entry| entry.encoded.clone())
+            .collect();
+        self.storage.save(&encoded);
+    }
+}
+
+#[derive(Clone)]
+pub struct ZomeCallQueue {
+    ws: ReconnectingAppWebsocket,
+    state: Rc<QueueState>,
+    wake: UnboundedSender<()>,
+}
+
+impl fmt::Debug for ZomeCallQueue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ZomeCallQueue")
+            .field("queued", &self.len())
+            .finish()
+    }
+}
+
+impl ZomeCallQueue {
+    /// loads any calls left in `storage` and starts sending them (& later ones) in the background.
+    pub fn new(ws: ReconnectingAppWebsocket, storage: impl QueueStorage + 'static) -> Self {
+        let mut entries = VecDeque::new();
+        let mut restored = Vec::new();
+        // entries which no longer decode are dropped rather than wedging the queue.
+        for encoded in storage.load() {
+            if let Some(cmd) = decode(&encoded) {
+                let (sender, receiver) = oneshot::channel();
+                entries.push_back(Entry {
+                    cmd,
+                    encoded,
+                    outcome: Some(sender),
+                });
+                restored.push(QueuedCall(receiver));
+            }
+        }
+        let state = Rc::new(QueueState {
+            entries: RefCell::new(entries),
+            storage: Box::new(storage),
+            restored: RefCell::new(restored),
+            pumping: Cell::new(true),
+        });
+        state.persist();
+        let (wake, woken) = unbounded();
+        let _ = wake.unbounded_send(());
+        spawn_local(pump(ws.clone(), state.clone(), woken));
+        ZomeCallQueue { ws, state, wake }
+    }
+
+    /// queues an idempotent zome call, to be sent once connected (and again, should the
+    /// connection drop before it completes). commands other than `CallZome` aren't queued, but
+    /// sent straight away as by `call`. a call which can't be stored (see the module docs) isn't
+    /// queued either, but resolves to the `CallError::Js` encoding it threw.
+    pub fn enqueue(&self, cmd: AppWsCmd) -> QueuedCall {
+        let (sender, receiver) = oneshot::channel();
+        match encode(&cmd) {
+            Ok(Some(encoded)) => {
+                // once closed, calls are only stored for next time.
+                let outcome = if self.is_running() {
+                    Some(sender)
+                } else {
+                    let _ = sender.send(Err(CallError::Closed));
+                    None
+                };
+                self.state.entries.borrow_mut().push_back(Entry {
+                    cmd,
+                    encoded,
+                    outcome,
+                });
+                self.state.persist();
+                let _ = self.wake.unbounded_send(());
+            }
+            Err(err) => {
+                let _ = sender.send(Err(err));
+            }
+            Ok(None) => {
+                let ws = self.ws.clone();
+                spawn_local(async move {
+                    let _ = sender.send(ws.call(cmd).await);
+                });
+            }
+        }
+        QueuedCall(receiver)
+    }
+
+    /// sends `cmd` once, bypassing the queue. use this for calls which mustn't be repeated.
+    pub async fn call(&self, cmd: AppWsCmd) -> Outcome {
+        self.ws.call(cmd).await
+    }
+
+    /// the outcomes of the calls which were loaded from storage. only returned once.
+    pub fn restored(&self) -> Vec<QueuedCall> {
+        self.state.restored.take()
+    }
+
+    /// the number of calls waiting to be sent or completed.
+    pub fn len(&self) -> usize {
+        self.state.entries.borrow().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// whether queued calls are still being sent. stops once the websocket is closed.
+    pub fn is_running(&self) -> bool {
+        self.state.pumping.get()
+    }
+}
+
+/// sends queued calls one at a time, oldest first, until every `ZomeCallQueue` handle is gone.
+async fn pump(
+    ws: ReconnectingAppWebsocket,
+    state: Rc<QueueState>,
+    mut woken: UnboundedReceiver<()>,
+) {
+    while woken.next().await.is_some() {
+        loop {
+            let cmd = match state.entries.borrow().front() {
+                Some(entry) => entry.cmd.clone(),
+                None => break,
+            };
+            if ws.connected().await.is_err() {
+                // closed for good. the calls stay in storage for next time.
+                state.pumping.set(false);
+                for entry in state.entries.borrow_mut().iter_mut() {
+                    if let Some(outcome) = entry.outcome.take() {
+                        let _ = outcome.send(Err(CallError::Closed));
+                    }
+                }
+                return;
+            }
+            let res = ws.call(cmd).await;
+            match res {
+                // picked up by `connected` above.
+                Err(CallError::Closed) => continue,
+                // the connection dropped under the call; send it again once reconnected.
+                Err(CallError::Js(_)) if ws.state() != ConnectionState::Open => continue,
+                _ => {}
+            }
+            let entry = state.entries.borrow_mut().pop_front();
+            state.persist();
+            if let Some(outcome) = entry.and_then(|entry| entry.outcome) {
+                let _ = outcome.send(res);
+            }
+        }
+    }
+    state.pumping.set(false);
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// encoding
+////////////////////////////////////////////////////////////////////////////////
+
+/// a `CallZome` as stored, or `None` for other commands, which aren't queued.
+fn encode(cmd: &AppWsCmd) -> Result<Option<String>, CallError> {
+    let (cell_id, zome_name, fn_name, payload, provenance, cap) = match cmd.clone() {
+        AppWsCmd::CallZome {
+            cell_id,
+            zome_name,
+            fn_name,
+            payload,
+            provenance,
+            cap,
+        } => (cell_id, zome_name, fn_name, payload, provenance, cap),
+        _ => return Ok(None),
+    };
+    let obj: JsValue = Object::new().into();
+    for (key, val) in [
+        ("cell_id", cell_id.serialize_to_js_obj()),
+        ("zome_name", zome_name.serialize_to_js_obj()),
+        ("fn_name", fn_name.serialize_to_js_obj()),
+        ("payload", payload),
+        ("provenance", provenance.serialize_to_js_obj()),
+        ("cap", cap.serialize_to_js_obj()),
+    ] {
+        assert!(
+            Reflect::set(&obj, &JsValue::from_str(key), &val).expect("object field set to succeed")
+        );
+    }
+    let encoded = JSON::stringify(&to_plain(&obj)).map_err(CallError::Js)?;
+    Ok(encoded.as_string())
+}
+
+fn decode(encoded: &str) -> Option<AppWsCmd> {
+    let obj = from_plain(&JSON::parse(encoded).ok()?);
+    let get = |key: &str| Reflect::get(&obj, &JsValue::from_str(key)).ok();
+    let string = |key: &str| get(key).and_then(|val| val.as_string());
+    let cell_id = get("cell_id").filter(|val| Array::from(val).length() == 2)?;
+    Some(AppWsCmd::CallZome {
+        cell_id: CellId::deserialize_from_js_obj(cell_id),
+        zome_name: string("zome_name")?,
+        fn_name: string("fn_name")?,
+        payload: get("payload")?,
+        provenance: AgentPk::deserialize_from_js_obj(get("provenance")?),
+        cap: match get("cap")?.dyn_into::<Uint8Array>() {
+            Ok(bytes) => Some(CapSecret::try_from(bytes.to_vec().as_slice()).ok()?),
+            Err(_) => None,
+        },
+    })
+}
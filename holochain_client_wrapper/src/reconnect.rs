@@ -0,0 +1,437 @@
+//! connections which survive conductor restarts.
+//!
+//! a `ReconnectingWebsocket` listens for its socket's `close` event and re-runs
+//! `connect_admin_ws`/`connect_app_ws` with exponential backoff until the conductor is back. calls
+//! made in the meantime wait for the new connection (within their deadline), and app signal
+//! subscriptions carry over from one connection to the next.
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    future::Future,
+    rc::{Rc, Weak},
+};
+
+use futures::{
+    future::{FutureExt, LocalBoxFuture},
+    StreamExt,
+};
+use js_sys::{Function, Reflect};
+use serde::de::DeserializeOwned;
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::spawn_local;
+
+use crate::{
+    broadcast::{Subscribers, Subscription},
+    call::{sleep, with_deadline},
+    connect_admin_ws, connect_app_ws_with_hub,
+    connection::Connection,
+    signal::SignalHub,
+    AdminWebsocket, AdminWsCmd, AdminWsCmdResponse, AppSignal, AppWebsocket, AppWsCmd,
+    AppWsCmdResponse, CallError, CallOptions, CellId, MalformedSignal, Signal, TypedSignals,
+    ZomeCallSigner,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConnectionState {
+    /// the first connection attempt is in progress.
+    Connecting,
+    Open,
+    /// the connection dropped, and is being re-established.
+    Reconnecting,
+    /// `close`d, or every connection attempt allowed by the `Backoff` failed. nothing more will
+    /// be tried.
+    Closed,
+}
+
+/// how long to wait between connection attempts.
+#[derive(Clone, Debug)]
+pub struct Backoff {
+    /// delay, in milliseconds, after the first failed attempt.
+    pub initial_delay: u32,
+    /// cap on the delay, in milliseconds.
+    pub max_delay: u32,
+    /// factor the delay grows by after each further failure.
+    pub multiplier: u32,
+    /// give up (and go `Closed`) after this many consecutive failures. `None` retries forever.
+    pub max_attempts: Option<u32>,
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Backoff {
+            initial_delay: 250,
+            max_delay: 30_000,
+            multiplier: 2,
+            max_attempts: None,
+        }
+    }
+}
+
+impl Backoff {
+    /// the delay after the `failures`th consecutive failed attempt (counting from 1).
+    pub fn delay(&self, failures: u32) -> u32 {
+        self.multiplier
+            .saturating_pow(failures.saturating_sub(1))
+            .saturating_mul(self.initial_delay)
+            .min(self.max_delay)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// ReconnectingWebsocket
+////////////////////////////////////////////////////////////////////////////////
+
+type Connect<W> = Box<dyn Fn() -> LocalBoxFuture<'static, Result<W, String>>>;
+
+struct Inner<W> {
+    connect: Connect<W>,
+    connection: fn(&W) -> &Rc<Connection>,
+    backoff: Backoff,
+    call_timeout: Option<u32>,
+    current: RefCell<Option<W>>,
+    /// bumped for every new connection, so a late `close` from an old socket is ignored.
+    generation: Cell<u64>,
+    state: Cell<ConnectionState>,
+    states: Rc<Subscribers<ConnectionState>>,
+}
+
+impl<W: Clone + 'static> Inner<W> {
+    fn set_state(&self, state: ConnectionState) {
+        self.state.set(state);
+        self.states.publish(state);
+    }
+
+    /// (re)connects until it succeeds or the backoff gives up. holds only a weak reference while
+    /// waiting, so dropping the last `ReconnectingWebsocket` stops the loop.
+    async fn run(weak: Weak<Inner<W>>) {
+        let mut failures = 0;
+        loop {
+            let connecting = match weak.upgrade() {
+                Some(inner) if inner.state.get() != ConnectionState::Closed => (inner.connect)(),
+                _ => return,
+            };
+            let res = connecting.await;
+            // a connection which opens after `close` is dropped, closing it again.
+            let inner = match weak.upgrade() {
+                Some(inner) if inner.state.get() != ConnectionState::Closed => inner,
+                _ => return,
+            };
+            match res {
+                Ok(ws) => {
+                    let generation = inner.generation.get() + 1;
+                    inner.generation.set(generation);
+                    on_close(&(inner.connection)(&ws).js_ws, weak.clone(), generation);
+                    *inner.current.borrow_mut() = Some(ws);
+                    inner.set_state(ConnectionState::Open);
+                    return;
+                }
+                Err(_) => {
+                    failures += 1;
+                    if inner
+                        .backoff
+                        .max_attempts
+                        .is_some_and(|max| failures >= max)
+                    {
+                        inner.set_state(ConnectionState::Closed);
+                        return;
+                    }
+                    let delay = inner.backoff.delay(failures);
+                    drop(inner);
+                    sleep(delay).await;
+                }
+            }
+        }
+    }
+
+    /// called when connection `generation` is found to be dead.
+    fn lost(self: &Rc<Self>, generation: u64) {
+        if generation != self.generation.get() || self.state.get() != ConnectionState::Open {
+            return;
+        }
+        self.set_state(ConnectionState::Reconnecting);
+        // dropped outside the borrow: closing it may fire its `close` listener right away.
+        let dead = self.current.borrow_mut().take();
+        drop(dead);
+        spawn_local(Inner::run(Rc::downgrade(self)));
+    }
+}
+
+/// `holochain-client-js` keeps its `isomorphic-ws` socket at `client.socket`.
+fn socket(js_ws: &JsValue) -> Option<JsValue> {
+    let client = Reflect::get(js_ws, &JsValue::from_str("client")).ok()?;
+    let socket = Reflect::get(&client, &JsValue::from_str("socket")).ok()?;
+    socket.is_object().then_some(socket)
+}
+
+/// whether the socket is `CLOSING` or `CLOSED`. unknown sockets are assumed to be alive.
+fn socket_closed(js_ws: &JsValue) -> bool {
+    socket(js_ws)
+        .and_then(|socket| Reflect::get(&socket, &JsValue::from_str("readyState")).ok())
+        .and_then(|ready_state| ready_state.as_f64())
+        .is_some_and(|ready_state| ready_state >= 2.0)
+}
+
+fn on_close<W: Clone + 'static>(js_ws: &JsValue, weak: Weak<Inner<W>>, generation: u64) {
+    let socket = match socket(js_ws) {
+        Some(socket) => socket,
+        None => return,
+    };
+    let add_event_listener = match Reflect::get(&socket, &JsValue::from_str("addEventListener"))
+        .ok()
+        .and_then(|f| f.dyn_into::<Function>().ok())
+    {
+        Some(f) => f,
+        None => return,
+    };
+    let listener = Closure::<dyn FnMut()>::new(move || {
+        if let Some(inner) = weak.upgrade() {
+            inner.lost(generation);
+        }
+    })
+    .into_js_value();
+    let _ = add_event_listener.call2(&socket, &JsValue::from_str("close"), &listener);
+}
+
+/// a websocket which reconnects by itself. cloning it shares the underlying connection.
+pub struct ReconnectingWebsocket<W> {
+    inner: Rc<Inner<W>>,
+    signal_hub: SignalHub,
+    signer: ZomeCallSigner,
+}
+
+pub type ReconnectingAdminWebsocket = ReconnectingWebsocket<AdminWebsocket>;
+pub type ReconnectingAppWebsocket = ReconnectingWebsocket<AppWebsocket>;
+
+impl<W> Clone for ReconnectingWebsocket<W> {
+    fn clone(&self) -> Self {
+        ReconnectingWebsocket {
+            inner: self.inner.clone(),
+            signal_hub: self.signal_hub.clone(),
+            signer: self.signer.clone(),
+        }
+    }
+}
+
+impl<W> fmt::Debug for ReconnectingWebsocket<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReconnectingWebsocket")
+            .field("state", &self.inner.state.get())
+            .field("backoff", &self.inner.backoff)
+            .finish()
+    }
+}
+
+impl<W: Clone + 'static> ReconnectingWebsocket<W> {
+    fn spawn(
+        connect: Connect<W>,
+        connection: fn(&W) -> &Rc<Connection>,
+        backoff: Backoff,
+        call_timeout: Option<u32>,
+        signal_hub: SignalHub,
+        signer: ZomeCallSigner,
+    ) -> Self {
+        let inner = Rc::new(Inner {
+            connect,
+            connection,
+            backoff,
+            call_timeout,
+            current: RefCell::new(None),
+            generation: Cell::new(0),
+            state: Cell::new(ConnectionState::Connecting),
+            states: Rc::default(),
+        });
+        spawn_local(Inner::run(Rc::downgrade(&inner)));
+        ReconnectingWebsocket {
+            inner,
+            signal_hub,
+            signer,
+        }
+    }
+
+    pub fn state(&self) -> ConnectionState {
+        self.inner.state.get()
+    }
+
+    /// every state change from now on.
+    pub fn state_changes(&self) -> Subscription<ConnectionState> {
+        self.inner.states.subscribe()
+    }
+
+    /// the connection, once it's open. fails with `CallError::Closed` if it never will be.
+    pub async fn connected(&self) -> Result<W, CallError> {
+        // subscribe before checking, so a change in between isn't missed.
+        let mut changes = self.state_changes();
+        loop {
+            match self.inner.state.get() {
+                ConnectionState::Open => {
+                    if let Some(ws) = self.inner.current.borrow().clone() {
+                        return Ok(ws);
+                    }
+                }
+                ConnectionState::Closed => return Err(CallError::Closed),
+                ConnectionState::Connecting | ConnectionState::Reconnecting => {}
+            }
+            if changes.next().await.is_none() {
+                return Err(CallError::Closed);
+            }
+        }
+    }
+
+    /// stops reconnecting & closes the current connection. pending & future calls fail with
+    /// `CallError::Closed`.
+    pub async fn close(&self) -> Result<(), CallError> {
+        if self.inner.state.get() == ConnectionState::Closed {
+            return Ok(());
+        }
+        self.inner.set_state(ConnectionState::Closed);
+        let current = self.inner.current.borrow_mut().take();
+        match current {
+            Some(ws) => (self.inner.connection)(&ws).close().await,
+            None => Ok(()),
+        }
+    }
+
+    /// waits for the connection & makes a call on it, all within one deadline. a call which fails
+    /// because the socket died triggers a reconnect straight away.
+    async fn call_with<R, F, Fut>(&self, opts: CallOptions, call: F) -> Result<R, CallError>
+    where
+        F: FnOnce(W) -> Fut,
+        Fut: Future<Output = Result<R, JsValue>>,
+    {
+        let attempt = async {
+            let ws = match self.connected().await {
+                Ok(ws) => ws,
+                Err(err) => return Ok(Err(err)),
+            };
+            let generation = self.inner.generation.get();
+            let connection = (self.inner.connection)(&ws).clone();
+            // guarded, so `close` fails the call rather than leaving it to its deadline.
+            let res = connection
+                .guard(call(ws).map(|res| res.map_err(CallError::Js)))
+                .await;
+            if matches!(res, Err(CallError::Js(_))) && socket_closed(&connection.js_ws) {
+                self.inner.lost(generation);
+            }
+            Ok(res)
+        };
+        let timeout = opts.timeout.or(self.inner.call_timeout);
+        with_deadline(attempt, timeout, opts.cancellation)
+            .await
+            .and_then(|res| res)
+    }
+}
+
+impl ReconnectingWebsocket<AdminWebsocket> {
+    /// starts connecting in the background; watch `state_changes` or await `connected` to know
+    /// when it's up. `timeout` bounds each connection attempt and is the default call deadline.
+    pub fn new(url: String, timeout: Option<u32>, backoff: Backoff) -> Self {
+        let connect: Connect<AdminWebsocket> =
+            Box::new(move || connect_admin_ws(url.clone(), timeout).boxed_local());
+        ReconnectingWebsocket::spawn(
+            connect,
+            |ws| &ws.connection,
+            backoff,
+            timeout,
+            SignalHub::default(),
+            ZomeCallSigner::default(),
+        )
+    }
+
+    pub async fn call(&self, cmd: AdminWsCmd) -> Result<AdminWsCmdResponse, CallError> {
+        self.call_with_options(cmd, CallOptions::default()).await
+    }
+
+    pub async fn call_with_options(
+        &self,
+        cmd: AdminWsCmd,
+        opts: CallOptions,
+    ) -> Result<AdminWsCmdResponse, CallError> {
+        self.call_with(opts, |ws| async move { ws.call_js(cmd).await })
+            .await
+    }
+}
+
+/// signal subscriptions are made on the wrapper rather than on any one connection, so they keep
+/// delivering across reconnects.
+impl ReconnectingWebsocket<AppWebsocket> {
+    /// starts connecting in the background; watch `state_changes` or await `connected` to know
+    /// when it's up. `timeout` bounds each connection attempt and is the default call deadline.
+    pub fn new(url: String, timeout: Option<u32>, backoff: Backoff) -> Self {
+        ReconnectingAppWebsocket::with_signer(url, timeout, backoff, ZomeCallSigner::default())
+    }
+
+    /// like `new`, signing zome calls with `signer`'s credentials, e.g. from an
+    /// `EncryptedKeyStore` loaded at startup.
+    pub fn with_signer(
+        url: String,
+        timeout: Option<u32>,
+        backoff: Backoff,
+        signer: ZomeCallSigner,
+    ) -> Self {
+        let signal_hub = SignalHub::default();
+        let (hub, sign_with) = (signal_hub.clone(), signer.clone());
+        let connect: Connect<AppWebsocket> = Box::new(move || {
+            let sign_with = sign_with.clone();
+            connect_app_ws_with_hub(url.clone(), timeout, hub.clone())
+                .map(move |res| res.map(|ws| ws.sign_zome_calls(sign_with)))
+                .boxed_local()
+        });
+        ReconnectingWebsocket::spawn(
+            connect,
+            |ws| &ws.connection,
+            backoff,
+            timeout,
+            signal_hub,
+            signer,
+        )
+    }
+
+    /// the credentials zome calls are signed with, on this & every later connection. calls to
+    /// cells without credentials go out unsigned.
+    pub fn signer(&self) -> &ZomeCallSigner {
+        &self.signer
+    }
+
+    pub async fn call(&self, cmd: AppWsCmd) -> Result<AppWsCmdResponse, CallError> {
+        self.call_with_options(cmd, CallOptions::default()).await
+    }
+
+    pub async fn call_with_options(
+        &self,
+        cmd: AppWsCmd,
+        opts: CallOptions,
+    ) -> Result<AppWsCmdResponse, CallError> {
+        self.call_with(opts, |ws| async move { ws.call_js(cmd).await })
+            .await
+    }
+
+    /// see `AppWebsocket::signals`.
+    pub fn signals(&self) -> Subscription<AppSignal> {
+        self.signal_hub.app.subscribe()
+    }
+
+    /// see `AppWebsocket::all_signals`.
+    pub fn all_signals(&self) -> Subscription<Signal> {
+        self.signal_hub.all.subscribe()
+    }
+
+    /// see `AppWebsocket::subscribe_typed`.
+    pub fn subscribe_typed<T: DeserializeOwned>(
+        &self,
+        cell_id: CellId,
+        zome_name: impl Into<String>,
+    ) -> TypedSignals<T> {
+        TypedSignals::new(
+            self.signal_hub.app.subscribe(),
+            cell_id,
+            zome_name.into(),
+            self.signal_hub.malformed.clone(),
+        )
+    }
+
+    /// see `AppWebsocket::malformed_signals`.
+    pub fn malformed_signals(&self) -> Subscription<MalformedSignal> {
+        self.signal_hub.malformed.subscribe()
+    }
+}
@@ -0,0 +1,362 @@
+//! record-and-replay of conductor traffic.
+//!
+//! both halves work at the boundary between this crate and `holochain-client-js`: a `Recording`
+//! wraps a live client's JS object so that every method call & its settled result is captured,
+//! and a `Replay` stands in for that JS object, answering from a recording. this means replayed
+//! sessions exercise exactly the same request-building & response-parsing code as live ones.
+//!
+//! recordings serialize to JSON. `Uint8Array`s (hashes, keys) are stored as `{ "$bytes": [..] }`
+//! and rejected `Error`s as `{ "$error": { name, message } }`.
+
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use js_sys::{Array, Function, JsString, Object, Promise, Reflect, Uint8Array, JSON};
+use wasm_bindgen::{prelude::*, JsCast};
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+use crate::{AdminWebsocket, AdminWsCmd, AppWebsocket, AppWsCmd};
+
+/// one client method call & how it settled.
+#[derive(Clone, Debug)]
+pub struct Exchange {
+    /// camelCase JS client method name, e.g. `attachAppInterface`.
+    pub method: String,
+    pub payload: JsValue,
+    pub result: Result<JsValue, JsValue>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Recording
+////////////////////////////////////////////////////////////////////////////////
+
+/// an append-only log of exchanges, shared between every websocket wrapped by it.
+#[derive(Clone, Debug, Default)]
+pub struct Recording {
+    exchanges: Rc<RefCell<Vec<Exchange>>>,
+}
+
+impl Recording {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn exchanges(&self) -> Vec<Exchange> {
+        self.exchanges.borrow().clone()
+    }
+
+    /// returns a websocket which forwards every call to `ws`, recording it.
+    pub fn admin_ws(&self, ws: &AdminWebsocket) -> AdminWebsocket {
+        ws.with_js_ws(self.wrap(&ws.js_ws, AdminWsCmd::METHOD_NAMES))
+    }
+
+    /// returns a websocket which forwards every call to `ws`, recording it.
+    pub fn app_ws(&self, ws: &AppWebsocket) -> AppWebsocket {
+        ws.with_js_ws(self.wrap(&ws.js_ws, AppWsCmd::METHOD_NAMES))
+    }
+
+    fn wrap(&self, real: &JsValue, method_names: &[&str]) -> JsValue {
+        let obj: JsValue = Object::new().into();
+        for method_name in method_names {
+            let real = real.clone();
+            let recording = self.clone();
+            let method_name = method_name.to_string();
+            let closure = Closure::<dyn FnMut(JsValue) -> Promise>::new({
+                let method_name = method_name.clone();
+                move |payload: JsValue| {
+                    let real = real.clone();
+                    let recording = recording.clone();
+                    let method_name = method_name.clone();
+                    future_to_promise(async move {
+                        let result = call_method(&real, &method_name, &payload).await;
+                        recording.exchanges.borrow_mut().push(Exchange {
+                            method: method_name,
+                            payload,
+                            result: result.clone(),
+                        });
+                        result
+                    })
+                }
+            });
+            assert!(Reflect::set(
+                &obj,
+                &JsValue::from_str(&method_name),
+                &closure.into_js_value(),
+            )
+            .expect("object field set to succeed"));
+        }
+        obj
+    }
+
+    pub fn to_json(&self) -> String {
+        let arr = Array::new();
+        for exchange in self.exchanges.borrow().iter() {
+            let obj: JsValue = Object::new().into();
+            let (result_key, result) = match &exchange.result {
+                Ok(val) => ("ok", val),
+                Err(err) => ("err", err),
+            };
+            for (key, val) in [
+                ("method", JsValue::from_str(&exchange.method)),
+                ("payload", to_plain(&exchange.payload)),
+                (result_key, to_plain(result)),
+            ] {
+                assert!(Reflect::set(&obj, &JsValue::from_str(key), &val)
+                    .expect("object field set to succeed"));
+            }
+            let _ = arr.push(&obj);
+        }
+        JSON::stringify_with_replacer_and_space(&arr, &JsValue::NULL, &JsValue::from(2))
+            .expect("JSON serialization to succeed")
+            .into()
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let parse = || -> Result<Vec<Exchange>, JsValue> {
+            let arr: Array = JSON::parse(json)?.dyn_into()?;
+            let mut exchanges = Vec::new();
+            for obj in arr.iter() {
+                let method: JsString =
+                    Reflect::get(&obj, &JsValue::from_str("method"))?.dyn_into()?;
+                let payload = from_plain(&Reflect::get(&obj, &JsValue::from_str("payload"))?);
+                let result = if Reflect::has(&obj, &JsValue::from_str("ok"))? {
+                    Ok(from_plain(&Reflect::get(&obj, &JsValue::from_str("ok"))?))
+                } else {
+                    Err(from_plain(&Reflect::get(&obj, &JsValue::from_str("err"))?))
+                };
+                exchanges.push(Exchange {
+                    method: method.into(),
+                    payload,
+                    result,
+                });
+            }
+            Ok(exchanges)
+        };
+        match parse() {
+            Ok(exchanges) => Ok(Recording {
+                exchanges: Rc::new(RefCell::new(exchanges)),
+            }),
+            Err(js_err) => Err(format!("{:?}", js_err)),
+        }
+    }
+}
+
+async fn call_method(
+    js_ws: &JsValue,
+    method_name: &str,
+    payload: &JsValue,
+) -> Result<JsValue, JsValue> {
+    let method: Function = Reflect::get(js_ws, &JsValue::from_str(method_name))?.dyn_into()?;
+    let promise: Promise = method.call1(js_ws, payload)?.dyn_into()?;
+    JsFuture::from(promise).await
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// Replay
+////////////////////////////////////////////////////////////////////////////////
+
+/// a fake client backend which answers calls from a recording, in order.
+///
+/// any call which doesn't match the next recorded exchange (by method name & payload) is
+/// rejected and remembered, so `verify` fails the test even if the UI swallowed the error.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    state: Rc<ReplayState>,
+}
+
+#[derive(Debug)]
+struct ReplayState {
+    expected: RefCell<VecDeque<Exchange>>,
+    failures: RefCell<Vec<String>>,
+}
+
+impl Replay {
+    pub fn new(recording: &Recording) -> Self {
+        Replay {
+            state: Rc::new(ReplayState {
+                expected: RefCell::new(recording.exchanges().into()),
+                failures: RefCell::new(Vec::new()),
+            }),
+        }
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        Recording::from_json(json).map(|recording| Self::new(&recording))
+    }
+
+    pub fn admin_ws(&self) -> AdminWebsocket {
+        self.backend(AdminWsCmd::METHOD_NAMES).into()
+    }
+
+    pub fn app_ws(&self) -> AppWebsocket {
+        self.backend(AppWsCmd::METHOD_NAMES).into()
+    }
+
+    /// the number of recorded exchanges not yet replayed.
+    pub fn remaining(&self) -> usize {
+        self.state.expected.borrow().len()
+    }
+
+    /// checks that every recorded exchange was replayed and that no unexpected call was made.
+    pub fn verify(&self) -> Result<(), String> {
+        let mut problems = self.state.failures.borrow().clone();
+        for exchange in self.state.expected.borrow().iter() {
+            problems.push(format!(
+                "replay: exchange never requested: {}",
+                describe(&exchange.method, &exchange.payload)
+            ));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("\n"))
+        }
+    }
+
+    fn backend(&self, method_names: &[&str]) -> JsValue {
+        let obj: JsValue = Object::new().into();
+        for method_name in method_names {
+            let state = self.state.clone();
+            let method_name = method_name.to_string();
+            let closure = Closure::<dyn FnMut(JsValue) -> Promise>::new({
+                let method_name = method_name.clone();
+                move |payload: JsValue| state.answer(&method_name, &payload)
+            });
+            assert!(Reflect::set(
+                &obj,
+                &JsValue::from_str(&method_name),
+                &closure.into_js_value(),
+            )
+            .expect("object field set to succeed"));
+        }
+        obj
+    }
+}
+
+impl ReplayState {
+    fn answer(&self, method_name: &str, payload: &JsValue) -> Promise {
+        let next = self.expected.borrow_mut().pop_front();
+        match next {
+            Some(exchange)
+                if exchange.method == method_name
+                    && plain_json(&exchange.payload) == plain_json(payload) =>
+            {
+                match exchange.result {
+                    Ok(val) => Promise::resolve(&val),
+                    Err(err) => Promise::reject(&err),
+                }
+            }
+            other => {
+                let expected = match &other {
+                    Some(exchange) => describe(&exchange.method, &exchange.payload),
+                    None => "end of recording".into(),
+                };
+                let msg = format!(
+                    "replay: unexpected request: {}, expected: {}",
+                    describe(method_name, payload),
+                    expected
+                );
+                // put the unmatched exchange back so later calls can still line up.
+                if let Some(exchange) = other {
+                    self.expected.borrow_mut().push_front(exchange);
+                }
+                self.failures.borrow_mut().push(msg.clone());
+                Promise::reject(&js_sys::Error::new(&msg).into())
+            }
+        }
+    }
+}
+
+fn describe(method_name: &str, payload: &JsValue) -> String {
+    format!(
+        "{}({})",
+        method_name,
+        plain_json(payload).unwrap_or_default()
+    )
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// JSON-safe conversions
+////////////////////////////////////////////////////////////////////////////////
+
+fn plain_json(val: &JsValue) -> Option<String> {
+    JSON::stringify(&to_plain(val))
+        .ok()
+        .and_then(|s| s.as_string())
+}
+
+fn tagged(tag: &str, val: &JsValue) -> JsValue {
+    let obj: JsValue = Object::new().into();
+    assert!(Reflect::set(&obj, &JsValue::from_str(tag), val).expect("object field set to succeed"));
+    obj
+}
+
+pub(crate) fn to_plain(val: &JsValue) -> JsValue {
+    if let Some(bytes) = val.dyn_ref::<Uint8Array>() {
+        let arr: Array = bytes.to_vec().into_iter().map(JsValue::from).collect();
+        tagged("$bytes", &arr)
+    } else if let Some(err) = val.dyn_ref::<js_sys::Error>() {
+        let obj: JsValue = Object::new().into();
+        let _ = Reflect::set(&obj, &JsValue::from_str("name"), &err.name());
+        let _ = Reflect::set(&obj, &JsValue::from_str("message"), &err.message());
+        tagged("$error", &obj)
+    } else if Array::is_array(val) {
+        let arr: Array = val.clone().unchecked_into();
+        arr.iter()
+            .map(|ele| to_plain(&ele))
+            .collect::<Array>()
+            .into()
+    } else if val.is_object() {
+        let obj: JsValue = Object::new().into();
+        for entry in Object::entries(val.unchecked_ref()).iter() {
+            let entry: Array = entry.unchecked_into();
+            let _ = Reflect::set(&obj, &entry.get(0), &to_plain(&entry.get(1)));
+        }
+        obj
+    } else {
+        val.clone()
+    }
+}
+
+pub(crate) fn from_plain(val: &JsValue) -> JsValue {
+    if Array::is_array(val) {
+        let arr: Array = val.clone().unchecked_into();
+        return arr
+            .iter()
+            .map(|ele| from_plain(&ele))
+            .collect::<Array>()
+            .into();
+    }
+    if !val.is_object() {
+        return val.clone();
+    }
+    let get = |key: &str| Reflect::get(val, &JsValue::from_str(key)).unwrap_or(JsValue::UNDEFINED);
+    let bytes = get("$bytes");
+    if Array::is_array(&bytes) {
+        let bytes: Vec<u8> = Array::from(&bytes)
+            .iter()
+            .map(|b| b.as_f64().unwrap_or_default() as u8)
+            .collect();
+        return Uint8Array::from(bytes.as_slice()).into();
+    }
+    let err = get("$error");
+    if err.is_object() {
+        let message = Reflect::get(&err, &JsValue::from_str("message"))
+            .ok()
+            .and_then(|m| m.as_string())
+            .unwrap_or_default();
+        let js_err = js_sys::Error::new(&message);
+        if let Some(name) = Reflect::get(&err, &JsValue::from_str("name"))
+            .ok()
+            .and_then(|n| n.as_string())
+        {
+            js_err.set_name(&name);
+        }
+        return js_err.into();
+    }
+    let obj: JsValue = Object::new().into();
+    for entry in Object::entries(val.unchecked_ref()).iter() {
+        let entry: Array = entry.unchecked_into();
+        let _ = Reflect::set(&obj, &entry.get(0), &from_plain(&entry.get(1)));
+    }
+    obj
+}
@@ -0,0 +1,129 @@
+//! retrying zome calls which failed for transient reasons.
+//!
+//! failures are classified by the conductor's error `type`, never by message text. writes which
+//! race another write to the same source chain ("head moved") are reported by the conductor as
+//! `internal_error`s, which the default policy therefore retries. guest errors & validation
+//! failures caught by the ribosome are `ribosome_error`s, which it doesn't, as they'd fail the same
+//! way again. validation failures of a commit are `internal_error`s too, so are retried until
+//! `max_attempts` runs out.
+//!
+//! timeouts aren't retried by default: a call which timed out may still have run, so retrying it
+//! is only safe for idempotent calls. add `ErrorKind::Timeout` to `RetryPolicy::retryable` to opt
+//! in.
+
+use std::future::Future;
+
+use crate::{
+    call::sleep, AppWebsocket, AppWsCmd, AppWsCmdResponse, Backoff, CallError, ConductorError,
+    ReconnectingAppWebsocket,
+};
+
+/// what a failed call failed with, for deciding whether to retry it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// the call's deadline passed.
+    Timeout,
+    /// the conductor responded with an error of this wire `type`, e.g. `internal_error`.
+    Conductor(String),
+    /// the JS client rejected the call without a conductor error, e.g. because its socket closed.
+    Client,
+}
+
+impl ErrorKind {
+    /// `None` for failures which are never worth retrying: cancelled calls & closed connections.
+    pub fn of(err: &CallError) -> Option<ErrorKind> {
+        match err {
+            CallError::Timeout => Some(ErrorKind::Timeout),
+            CallError::Js(val) => Some(match ConductorError::from_js(val) {
+                Some(conductor_error) => ErrorKind::Conductor(conductor_error.error_type),
+                None => ErrorKind::Client,
+            }),
+            CallError::Cancelled | CallError::Closed => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// attempts in total, including the first. `1` never retries.
+    pub max_attempts: u32,
+    /// delays between attempts. its `max_attempts` is ignored in favour of the policy's.
+    pub backoff: Backoff,
+    /// the kinds of failure worth another attempt.
+    pub retryable: Vec<ErrorKind>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            backoff: Backoff {
+                initial_delay: 100,
+                max_delay: 2_000,
+                ..Backoff::default()
+            },
+            retryable: vec![ErrorKind::Conductor("internal_error".into())],
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// a single attempt.
+    pub fn never() -> Self {
+        RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    pub fn is_retryable(&self, err: &CallError) -> bool {
+        ErrorKind::of(err).is_some_and(|kind| self.retryable.contains(&kind))
+    }
+
+    /// runs `attempt` until it succeeds, fails with a non-retryable error, or runs out of
+    /// attempts; returning the last result.
+    pub async fn run<T, F, Fut>(&self, mut attempt: F) -> Result<T, CallError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, CallError>>,
+    {
+        let mut failures = 0;
+        loop {
+            match attempt().await {
+                Err(err) if failures + 1 < self.max_attempts && self.is_retryable(&err) => {
+                    failures += 1;
+                    sleep(self.backoff.delay(failures)).await;
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+impl AppWebsocket {
+    /// `call`, retried according to `policy`. each attempt gets its own deadline.
+    ///
+    /// a failed attempt may still have committed (e.g. the response was lost), so only retry
+    /// idempotent calls, particularly if `policy` retries timeouts or client errors.
+    pub async fn call_with_retry(
+        &self,
+        cmd: AppWsCmd,
+        policy: &RetryPolicy,
+    ) -> Result<AppWsCmdResponse, CallError> {
+        policy.run(|| self.call(cmd.clone())).await
+    }
+}
+
+impl ReconnectingAppWebsocket {
+    /// `call`, retried according to `policy`. each attempt gets its own deadline.
+    ///
+    /// a failed attempt may still have committed (e.g. the response was lost), so only retry
+    /// idempotent calls, particularly if `policy` retries timeouts or client errors.
+    pub async fn call_with_retry(
+        &self,
+        cmd: AppWsCmd,
+        policy: &RetryPolicy,
+    ) -> Result<AppWsCmdResponse, CallError> {
+        policy.run(|| self.call(cmd.clone())).await
+    }
+}
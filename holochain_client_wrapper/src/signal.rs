@@ -0,0 +1,226 @@
+//! signals pushed by the conductor over an app websocket.
+//!
+//! zomes emit app signals, while the conductor itself emits system signals (e.g. countersigning
+//! session events) on the same channel.
+
+use std::{
+    marker::PhantomData,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll},
+};
+
+use futures::{ready, Stream};
+use js_sys::{Array, Object, Reflect};
+use serde::de::DeserializeOwned;
+use wasm_bindgen::{prelude::*, JsCast};
+
+use crate::{
+    broadcast::{Subscribers, Subscription},
+    AgentPk, CellId, DeserializeFromJsObj, DnaHash, EntryHashRaw,
+};
+
+#[derive(Clone, Debug)]
+pub enum Signal {
+    App(AppSignal),
+    System(SystemSignal),
+}
+
+impl Signal {
+    /// system signals arrive either as `{ type: "System", data }` or `{ System: data }`, depending
+    /// on the `holochain-client-js` version; anything else is parsed as an app signal.
+    pub(crate) fn from_js(val: &JsValue) -> Result<Signal, String> {
+        let get = |key: &str| Reflect::get(val, &JsValue::from_str(key)).ok();
+        let system = match get("type").and_then(|ty| ty.as_string()).as_deref() {
+            Some("System") => get("data"),
+            _ => get("System").filter(|data| !data.is_undefined()),
+        };
+        match system {
+            Some(data) => SystemSignal::from_js(&data).map(Signal::System),
+            None => AppSignal::from_js(val).map(Signal::App),
+        }
+    }
+}
+
+/// a signal emitted by a zome via `emit_signal`.
+#[derive(Clone, Debug)]
+pub struct AppSignal {
+    /// the cell whose zome emitted the signal.
+    pub cell_id: CellId,
+    /// the emitting zome. only reported by newer versions of `holochain-client-js`, as `zomeName`
+    /// or `zome_name`.
+    pub zome_name: Option<String>,
+    /// the signal payload, already msgpack-decoded by the JS client.
+    pub payload: JsValue,
+}
+
+impl AppSignal {
+    /// parses the `{ type, data: { cellId, zomeName?, payload } }` object which
+    /// `holochain-client-js` hands to its signal callback. returns a description of the problem if
+    /// it isn't shaped like that.
+    pub(crate) fn from_js(val: &JsValue) -> Result<AppSignal, String> {
+        let parse = || -> Result<Option<AppSignal>, JsValue> {
+            let data = Reflect::get(val, &JsValue::from_str("data"))?;
+            let cell_id: Array = Reflect::get(&data, &JsValue::from_str("cellId"))?.dyn_into()?;
+            if cell_id.length() != 2 {
+                return Ok(None);
+            }
+            let zome_name = match Reflect::get(&data, &JsValue::from_str("zomeName"))?.as_string() {
+                Some(zome_name) => Some(zome_name),
+                None => Reflect::get(&data, &JsValue::from_str("zome_name"))?.as_string(),
+            };
+            let payload = Reflect::get(&data, &JsValue::from_str("payload"))?;
+            Ok(Some(AppSignal {
+                cell_id: (
+                    DnaHash::deserialize_from_js_obj(cell_id.get(0)),
+                    AgentPk::deserialize_from_js_obj(cell_id.get(1)),
+                ),
+                zome_name,
+                payload,
+            }))
+        };
+        match parse() {
+            Ok(Some(signal)) => Ok(signal),
+            Ok(None) => Err("cellId is not a [DnaHash, AgentPubKey] pair".into()),
+            Err(js_err) => Err(format!("{:?}", js_err)),
+        }
+    }
+}
+
+/// a signal emitted by the conductor itself.
+#[derive(Clone, Debug)]
+pub enum SystemSignal {
+    /// a countersigning session this agent took part in completed; carries the entry hash of the
+    /// countersigned entry.
+    SuccessfulCountersigning(EntryHashRaw),
+    /// a countersigning session was abandoned before completing.
+    AbandonedCountersigning(EntryHashRaw),
+    /// a system signal this crate doesn't model yet, as its variant name & data.
+    Other(String, JsValue),
+}
+
+impl SystemSignal {
+    /// parses an externally-tagged system signal, e.g. `{ SuccessfulCountersigning: hash }`.
+    fn from_js(val: &JsValue) -> Result<SystemSignal, String> {
+        let obj: &Object = val
+            .dyn_ref()
+            .ok_or_else(|| format!("system signal is not an object: {:?}", val))?;
+        let tag = Object::keys(obj)
+            .get(0)
+            .as_string()
+            .ok_or_else(|| format!("system signal has no variant: {:?}", val))?;
+        let data = Reflect::get(val, &JsValue::from_str(&tag)).map_err(|e| format!("{:?}", e))?;
+        Ok(match tag.as_str() {
+            "SuccessfulCountersigning" => {
+                SystemSignal::SuccessfulCountersigning(EntryHashRaw::deserialize_from_js_obj(data))
+            }
+            "AbandonedCountersigning" => {
+                SystemSignal::AbandonedCountersigning(EntryHashRaw::deserialize_from_js_obj(data))
+            }
+            _ => SystemSignal::Other(tag, data),
+        })
+    }
+}
+
+/// a signal which was dropped because it couldn't be parsed or decoded.
+#[derive(Clone, Debug)]
+pub struct MalformedSignal {
+    /// the raw signal, or its payload if only decoding into a typed subscription failed.
+    pub raw: JsValue,
+    pub reason: String,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// SignalHub
+////////////////////////////////////////////////////////////////////////////////
+
+/// the subscribers to one app websocket's signals.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SignalHub {
+    pub(crate) app: Rc<Subscribers<AppSignal>>,
+    pub(crate) all: Rc<Subscribers<Signal>>,
+    pub(crate) malformed: Rc<Subscribers<MalformedSignal>>,
+}
+
+impl SignalHub {
+    /// a signal callback for `AppWebsocket.connect`. the JS client owns it from then on, and
+    /// keeps the subscribers alive with it.
+    pub(crate) fn callback(&self) -> JsValue {
+        let hub = self.clone();
+        Closure::<dyn FnMut(JsValue)>::new(move |val: JsValue| hub.dispatch(val)).into_js_value()
+    }
+
+    fn dispatch(&self, val: JsValue) {
+        // signals we can't make sense of are reported rather than taking down the page.
+        match Signal::from_js(&val) {
+            Ok(Signal::App(signal)) => {
+                self.app.publish(signal.clone());
+                self.all.publish(Signal::App(signal));
+            }
+            Ok(signal) => self.all.publish(signal),
+            Err(reason) => self.malformed.publish(MalformedSignal { raw: val, reason }),
+        }
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// TypedSignals
+////////////////////////////////////////////////////////////////////////////////
+
+/// signals from one cell & zome, with payloads decoded into `T`.
+///
+/// signals whose payload doesn't decode are skipped and published as `MalformedSignal`s instead.
+/// signals without a zome name (from JS clients which don't report them) are skipped too: they
+/// could be any zome's, and decoding another zome's payload as `T` would either yield bogus values
+/// or flood `malformed_signals`. use `AppWebsocket::signals` to receive them.
+pub struct TypedSignals<T> {
+    signals: Subscription<AppSignal>,
+    cell_id: CellId,
+    zome_name: String,
+    malformed: Rc<Subscribers<MalformedSignal>>,
+    _payload: PhantomData<fn() -> T>,
+}
+
+impl<T> TypedSignals<T> {
+    pub(crate) fn new(
+        signals: Subscription<AppSignal>,
+        cell_id: CellId,
+        zome_name: String,
+        malformed: Rc<Subscribers<MalformedSignal>>,
+    ) -> Self {
+        TypedSignals {
+            signals,
+            cell_id,
+            zome_name,
+            malformed,
+            _payload: PhantomData,
+        }
+    }
+
+    fn wants(&self, signal: &AppSignal) -> bool {
+        signal.cell_id == self.cell_id && signal.zome_name.as_ref() == Some(&self.zome_name)
+    }
+}
+
+impl<T: DeserializeOwned> Stream for TypedSignals<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        loop {
+            let signal = match ready!(Pin::new(&mut self.signals).poll_next(cx)) {
+                None => return Poll::Ready(None),
+                Some(signal) => signal,
+            };
+            if !self.wants(&signal) {
+                continue;
+            }
+            match serde_wasm_bindgen::from_value(signal.payload.clone()) {
+                Ok(val) => return Poll::Ready(Some(val)),
+                Err(err) => self.malformed.publish(MalformedSignal {
+                    raw: signal.payload,
+                    reason: format!("zome {}: {}", self.zome_name, err),
+                }),
+            }
+        }
+    }
+}
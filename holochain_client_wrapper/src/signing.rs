@@ -0,0 +1,366 @@
+//! signing zome calls with a local ed25519 keypair, as newer conductors require.
+//!
+//! a keypair is authorized for a cell by granting its public key (as an agent key) an assigned
+//! capability through the admin websocket. an `AppWebsocket` given the resulting credentials via
+//! a `ZomeCallSigner` then signs every `CallZome` to that cell before sending it: the request's
+//! provenance & cap secret are replaced by the credentials', and a fresh nonce, an expiry and a
+//! signature over the hashed call are added.
+
+use std::{fmt, rc::Rc};
+
+use ed25519_dalek::{Signer, SigningKey};
+use js_sys::{Array, Date, Function, Object, Promise, Reflect, Uint8Array};
+use serde::Serialize;
+use serde_bytes::Bytes;
+use wasm_bindgen::{prelude::*, JsCast};
+use zeroize::Zeroizing;
+
+use crate::{
+    keystore::{MemoryKeyStore, SigningKeyStore},
+    AdminWebsocket, AgentPk, AppWebsocket, CallError, CapAccess, CapSecret, CellId,
+    DeserializeFromJsObj, SerializeToJsObj, ZomeCallCapGrant,
+};
+
+/// how long a signed call stays valid for, in milliseconds. matches `holochain-client-js`.
+const EXPIRY: f64 = 5.0 * 60.0 * 1000.0;
+
+/// the tag of the grants made by `AdminWebsocket::authorize_signing_credentials`.
+pub const SIGNING_GRANT_TAG: &str = "zome-call-signing-key";
+
+////////////////////////////////////////////////////////////////////////////////
+// SigningCredentials
+////////////////////////////////////////////////////////////////////////////////
+
+/// a signing keypair & the cap secret it was authorized with.
+#[derive(Clone)]
+pub struct SigningCredentials {
+    cap_secret: CapSecret,
+    keypair: SigningKey,
+}
+
+impl SigningCredentials {
+    /// a fresh keypair & cap secret, from the platform's CSPRNG. these still need authorizing
+    /// (see `AdminWebsocket::authorize_signing_credentials`) before the conductor accepts them.
+    pub fn generate() -> Self {
+        let mut seed = Zeroizing::new([0; 32]);
+        getrandom::getrandom(&mut *seed).expect("a source of randomness to be available");
+        SigningCredentials {
+            cap_secret: CapSecret::random(),
+            keypair: SigningKey::from_bytes(&seed),
+        }
+    }
+
+    pub fn cap_secret(&self) -> &CapSecret {
+        &self.cap_secret
+    }
+
+    /// the keypair's public key, as the agent key calls are made with.
+    pub fn signing_key(&self) -> AgentPk {
+        AgentPk(
+            Uint8Array::from(&agent_pub_key(self.keypair.verifying_key().as_bytes())[..]).into(),
+        )
+    }
+
+    /// the ed25519 signature of `call`'s `data_to_sign`.
+    pub fn sign(&self, call: &ZomeCallUnsigned) -> [u8; 64] {
+        self.keypair.sign(&call.data_to_sign()).to_bytes()
+    }
+
+    /// the keypair's seed followed by the cap secret, for key stores to keep. never handed to
+    /// application code.
+    pub(crate) fn to_secret_bytes(&self) -> Zeroizing<Vec<u8>> {
+        Zeroizing::new([&self.keypair.to_bytes()[..], self.cap_secret.as_bytes()].concat())
+    }
+
+    pub(crate) fn from_secret_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() != 32 + CapSecret::LEN {
+            return None;
+        }
+        let (seed, cap_secret) = bytes.split_at(32);
+        Some(SigningCredentials {
+            cap_secret: CapSecret::try_from(cap_secret).ok()?,
+            keypair: SigningKey::from_bytes(seed.try_into().ok()?),
+        })
+    }
+}
+
+/// deliberately doesn't print the keypair or secret.
+impl fmt::Debug for SigningCredentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningCredentials")
+            .field("signing_key", &self.signing_key())
+            .finish_non_exhaustive()
+    }
+}
+
+/// a holochain `AgentPubKey` for an ed25519 public key: the 3 byte agent prefix, the key, then
+/// its 4 byte DHT location (a 16 byte blake2b hash of the key, xor-folded).
+pub(crate) fn agent_pub_key(public_key: &[u8; 32]) -> Vec<u8> {
+    let hash = blake2b_simd::Params::new().hash_length(16).hash(public_key);
+    let mut location = [0; 4];
+    for (i, byte) in hash.as_bytes().iter().enumerate() {
+        location[i % 4] ^= byte;
+    }
+    [&[0x84, 0x20, 0x24][..], public_key, &location].concat()
+}
+
+impl AdminWebsocket {
+    /// generates signing credentials and grants them the right to call `functions`, as
+    /// `(zome name, function name)` pairs, in `cell_id`.
+    pub async fn authorize_signing_credentials(
+        &self,
+        cell_id: CellId,
+        functions: Vec<(String, String)>,
+    ) -> Result<SigningCredentials, CallError> {
+        let credentials = SigningCredentials::generate();
+        let access = CapAccess::Assigned {
+            secret: credentials.cap_secret.clone(),
+            assignees: vec![credentials.signing_key()],
+        };
+        let grant = ZomeCallCapGrant::new(SIGNING_GRANT_TAG, access, functions);
+        self.grant_capability(cell_id, grant).await?;
+        Ok(credentials)
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// ZomeCallUnsigned
+////////////////////////////////////////////////////////////////////////////////
+
+/// a zome call as it's signed: the conductor recomputes `data_to_sign` from the signed request
+/// to check its signature.
+#[derive(Clone, Debug)]
+pub struct ZomeCallUnsigned {
+    pub provenance: AgentPk,
+    pub cell_id: CellId,
+    pub zome_name: String,
+    pub fn_name: String,
+    pub cap_secret: Option<CapSecret>,
+    /// the msgpack-encoded payload.
+    pub payload: Vec<u8>,
+    pub nonce: [u8; 32],
+    /// microseconds since the unix epoch.
+    pub expires_at: i64,
+}
+
+/// mirrors the conductor's `ZomeCallUnsigned`, field order included.
+#[derive(Serialize)]
+struct ZomeCallUnsignedWire<'a> {
+    provenance: &'a Bytes,
+    cell_id: (&'a Bytes, &'a Bytes),
+    zome_name: &'a str,
+    fn_name: &'a str,
+    cap_secret: Option<&'a Bytes>,
+    payload: &'a Bytes,
+    nonce: &'a Bytes,
+    expires_at: i64,
+}
+
+impl ZomeCallUnsigned {
+    /// the blake2b-256 hash of the call's msgpack encoding.
+    pub fn data_to_sign(&self) -> [u8; 32] {
+        let provenance = js_bytes(&self.provenance.0);
+        let (dna_hash, agent_pk) = (js_bytes(&self.cell_id.0 .0), js_bytes(&self.cell_id.1 .0));
+        let encoded = rmp_serde::to_vec_named(&ZomeCallUnsignedWire {
+            provenance: Bytes::new(&provenance),
+            cell_id: (Bytes::new(&dna_hash), Bytes::new(&agent_pk)),
+            zome_name: &self.zome_name,
+            fn_name: &self.fn_name,
+            cap_secret: self
+                .cap_secret
+                .as_ref()
+                .map(|secret| Bytes::new(secret.as_bytes())),
+            payload: Bytes::new(&self.payload),
+            nonce: Bytes::new(&self.nonce),
+            expires_at: self.expires_at,
+        })
+        .expect("msgpack encoding to succeed");
+        let hash = blake2b_simd::Params::new().hash_length(32).hash(&encoded);
+        hash.as_bytes().try_into().expect("a 32 byte hash")
+    }
+
+    /// the request `callZome` takes, with `signature` added.
+    fn into_signed_js(self, signature: [u8; 64]) -> JsValue {
+        let obj: JsValue = Object::new().into();
+        for (key, val) in [
+            ("cell_id", self.cell_id.serialize_to_js_obj()),
+            ("zome_name", self.zome_name.serialize_to_js_obj()),
+            ("fn_name", self.fn_name.serialize_to_js_obj()),
+            ("payload", Uint8Array::from(&self.payload[..]).into()),
+            ("cap_secret", self.cap_secret.serialize_to_js_obj()),
+            ("provenance", self.provenance.serialize_to_js_obj()),
+            ("nonce", Uint8Array::from(&self.nonce[..]).into()),
+            ("expires_at", JsValue::from_f64(self.expires_at as f64)),
+            ("signature", Uint8Array::from(&signature[..]).into()),
+        ] {
+            assert!(Reflect::set(&obj, &JsValue::from_str(key), &val)
+                .expect("object field set to succeed"));
+        }
+        obj
+    }
+}
+
+fn js_bytes(val: &JsValue) -> Vec<u8> {
+    val.dyn_ref::<Uint8Array>()
+        .map(|arr| arr.to_vec())
+        .unwrap_or_default()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// msgpack payloads
+////////////////////////////////////////////////////////////////////////////////
+
+/// encodes a JS value as `@msgpack/msgpack` would: `Uint8Array`s as binary, whole numbers as
+/// integers, objects as maps (in key order) and `null`/`undefined` as nil.
+pub fn encode_payload(val: &JsValue) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    rmpv::encode::write_value(&mut buf, &to_msgpack(val)?)
+        .map_err(|err| format!("msgpack encoding failed: {}", err))?;
+    Ok(buf)
+}
+
+fn to_msgpack(val: &JsValue) -> Result<rmpv::Value, String> {
+    use rmpv::Value;
+
+    const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+    if val.is_null() || val.is_undefined() {
+        Ok(Value::Nil)
+    } else if let Some(b) = val.as_bool() {
+        Ok(Value::Boolean(b))
+    } else if let Some(n) = val.as_f64() {
+        Ok(if n.fract() == 0.0 && n.abs() <= MAX_SAFE_INTEGER {
+            if n >= 0.0 {
+                Value::from(n as u64)
+            } else {
+                Value::from(n as i64)
+            }
+        } else {
+            Value::F64(n)
+        })
+    } else if let Some(s) = val.as_string() {
+        Ok(Value::from(s))
+    } else if let Some(bytes) = val.dyn_ref::<Uint8Array>() {
+        Ok(Value::Binary(bytes.to_vec()))
+    } else if Array::is_array(val) {
+        Array::from(val)
+            .iter()
+            .map(|el| to_msgpack(&el))
+            .collect::<Result<_, _>>()
+            .map(Value::Array)
+    } else if val.is_object() && !val.is_function() {
+        Object::entries(val.unchecked_ref())
+            .iter()
+            .map(|entry| {
+                let entry = Array::from(&entry);
+                Ok((to_msgpack(&entry.get(0))?, to_msgpack(&entry.get(1))?))
+            })
+            .collect::<Result<_, String>>()
+            .map(Value::Map)
+    } else {
+        Err(format!("can't encode {:?} as msgpack", val))
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// ZomeCallSigner
+////////////////////////////////////////////////////////////////////////////////
+
+/// signs zome calls with the credentials `store` holds for each cell. clones share the same
+/// store, so cells authorized later are picked up by websockets already signing with it.
+#[derive(Clone)]
+pub struct ZomeCallSigner(Rc<dyn SigningKeyStore>);
+
+/// keeps its credentials in memory only.
+impl Default for ZomeCallSigner {
+    fn default() -> Self {
+        ZomeCallSigner::new(MemoryKeyStore::default())
+    }
+}
+
+impl fmt::Debug for ZomeCallSigner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ZomeCallSigner(..)")
+    }
+}
+
+impl ZomeCallSigner {
+    pub fn new(store: impl SigningKeyStore + 'static) -> Self {
+        ZomeCallSigner(Rc::new(store))
+    }
+
+    /// signs calls to `cell_id` with `credentials` from now on, replacing any it had.
+    pub fn add(&self, cell_id: CellId, credentials: SigningCredentials) {
+        self.0.set(cell_id, credentials);
+    }
+
+    pub fn remove(&self, cell_id: &CellId) -> Option<SigningCredentials> {
+        self.0.remove(cell_id)
+    }
+
+    pub fn get(&self, cell_id: &CellId) -> Option<SigningCredentials> {
+        self.0.get(cell_id)
+    }
+
+    /// signs a `callZome` request, as built for `AppWsCmd::CallZome`. requests to cells without
+    /// credentials are returned as they are.
+    pub fn sign_request(&self, request: &JsValue) -> Result<JsValue, String> {
+        let get = |key: &str| {
+            Reflect::get(request, &JsValue::from_str(key))
+                .map_err(|_| format!("zome call request has no {}", key))
+        };
+        let string = |key: &str| {
+            get(key)?
+                .as_string()
+                .ok_or_else(|| format!("zome call request's {} isn't a string", key))
+        };
+        let cell_id = get("cell_id")?;
+        if Array::from(&cell_id).length() != 2 {
+            return Err("zome call request's cell_id isn't a pair".into());
+        }
+        let cell_id = CellId::deserialize_from_js_obj(cell_id);
+        let credentials = match self.get(&cell_id) {
+            Some(credentials) => credentials,
+            None => return Ok(request.clone()),
+        };
+        let mut nonce = [0; 32];
+        getrandom::getrandom(&mut nonce).expect("a source of randomness to be available");
+        let call = ZomeCallUnsigned {
+            provenance: credentials.signing_key(),
+            cell_id,
+            zome_name: string("zome_name")?,
+            fn_name: string("fn_name")?,
+            cap_secret: Some(credentials.cap_secret.clone()),
+            payload: encode_payload(&get("payload")?)?,
+            nonce,
+            expires_at: ((Date::now() + EXPIRY) * 1000.0) as i64,
+        };
+        let signature = credentials.sign(&call);
+        Ok(call.into_signed_js(signature))
+    }
+}
+
+impl AppWebsocket {
+    /// the same connection, signing its zome calls with `signer`'s credentials.
+    pub fn sign_zome_calls(&self, signer: ZomeCallSigner) -> AppWebsocket {
+        // everything but `callZome` is inherited from the real client.
+        let wrapper: Object = Object::create(self.js_ws.unchecked_ref());
+        let real = self.js_ws.clone();
+        let call_zome = Closure::<dyn FnMut(JsValue) -> Result<Promise, JsValue>>::new(
+            move |request: JsValue| {
+                let signed = signer
+                    .sign_request(&request)
+                    .map_err(|err| JsValue::from(js_sys::Error::new(&err)))?;
+                let method: Function =
+                    Reflect::get(&real, &JsValue::from_str("callZome"))?.dyn_into()?;
+                method.call1(&real, &signed)?.dyn_into()
+            },
+        );
+        assert!(Reflect::set(
+            &wrapper,
+            &JsValue::from_str("callZome"),
+            &call_zome.into_js_value(),
+        )
+        .expect("object field set to succeed"));
+        self.with_js_ws(wrapper.into())
+    }
+}
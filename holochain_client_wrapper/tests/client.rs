@@ -0,0 +1,2319 @@
+//! exercises `generate_call`'s method dispatch, payload construction & response parsing against
+//! `tests/js/holochain_client_wrapper_stub.js`.
+//!
+//! run with `wasm-pack test --node --features js-stub`.
+
+#![cfg(target_arch = "wasm32")]
+
+use futures::StreamExt;
+use holochain_client_wrapper::{keystore::*, queue::*, record::*, *};
+use js_sys::{Array, Function, Object, Reflect, Uint8Array};
+use serde::Deserialize;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_test::wasm_bindgen_test;
+
+////////////////////////////////////////////////////////////////////////////////
+// stub control
+////////////////////////////////////////////////////////////////////////////////
+
+fn stub() -> JsValue {
+    Reflect::get(&js_sys::global(), &"__hcStub".into()).unwrap()
+}
+
+fn reset_stub() {
+    let state: JsValue = Object::new().into();
+    Reflect::set(&state, &"calls".into(), &Array::new()).unwrap();
+    Reflect::set(&state, &"responses".into(), &Object::new()).unwrap();
+    Reflect::set(&state, &"errors".into(), &Object::new()).unwrap();
+    Reflect::set(&state, &"pending".into(), &Array::new()).unwrap();
+    Reflect::set(&state, &"rejections".into(), &Object::new()).unwrap();
+    Reflect::set(&state, &"sockets".into(), &Array::new()).unwrap();
+    Reflect::set(&state, &"connectFailures".into(), &0.into()).unwrap();
+    Reflect::set(&js_sys::global(), &"__hcStub".into(), &state).unwrap();
+}
+
+fn respond(method: &str, val: &JsValue) {
+    Reflect::set(&get(&stub(), "responses"), &method.into(), val).unwrap();
+}
+
+fn reject(method: &str, val: &JsValue) {
+    Reflect::set(&get(&stub(), "errors"), &method.into(), val).unwrap();
+}
+
+/// makes the next calls to `method` reject, one per error, before falling back to `respond`.
+fn reject_next(method: &str, errors: &[JsValue]) {
+    let errors: Array = errors.iter().collect();
+    Reflect::set(&get(&stub(), "rejections"), &method.into(), &errors).unwrap();
+}
+
+/// a conductor error response, as rejected by `holochain-client-js`.
+fn conductor_error(error_type: &str, message: &str) -> JsValue {
+    obj(&[
+        ("type", "error".into()),
+        (
+            "data",
+            obj(&[("type", error_type.into()), ("data", message.into())]),
+        ),
+    ])
+}
+
+/// makes calls to `method` return a promise which never settles.
+fn never_settle(method: &str) {
+    Array::from(&get(&stub(), "pending")).push(&method.into());
+}
+
+/// the (method name, args) of the most recent call made to a stub client.
+fn last_call() -> (String, Array) {
+    let calls: Array = get(&stub(), "calls").dyn_into().unwrap();
+    let call = calls.at(-1);
+    (
+        get(&call, "method").as_string().unwrap(),
+        get(&call, "args").dyn_into().unwrap(),
+    )
+}
+
+/// pushes `signal` through the callback the library registered with `AppWebsocket.connect`.
+fn emit_signal(signal: &JsValue) {
+    let signal_cb: Function = get(&stub(), "signalCb").dyn_into().unwrap();
+    signal_cb.call1(&JsValue::NULL, signal).unwrap();
+}
+
+fn app_signal(dna_hash: &[u8], agent_pk: &[u8], payload: JsValue) -> JsValue {
+    let cell_id = Array::of2(&bytes(dna_hash), &bytes(agent_pk));
+    obj(&[
+        ("type", "Signal".into()),
+        (
+            "data",
+            obj(&[("cellId", cell_id.into()), ("payload", payload)]),
+        ),
+    ])
+}
+
+fn zome_signal(dna_hash: &[u8], zome_name: &str, payload: JsValue) -> JsValue {
+    let signal = app_signal(dna_hash, &[2], payload);
+    Reflect::set(&get(&signal, "data"), &"zomeName".into(), &zome_name.into()).unwrap();
+    signal
+}
+
+/// makes the next `n` connects reject.
+fn fail_connects(n: u32) {
+    Reflect::set(&stub(), &"connectFailures".into(), &n.into()).unwrap();
+}
+
+/// drops the most recently opened stub connection, as a conductor restart would.
+fn close_socket() {
+    let socket = Array::from(&get(&stub(), "sockets")).at(-1);
+    let close: Function = get(&socket, "close").dyn_into().unwrap();
+    close.call0(&socket).unwrap();
+}
+
+fn call_count(method: &str) -> usize {
+    Array::from(&get(&stub(), "calls"))
+        .iter()
+        .filter(|call| get(call, "method").as_string().unwrap() == method)
+        .count()
+}
+
+fn get(obj: &JsValue, key: &str) -> JsValue {
+    Reflect::get(obj, &key.into()).unwrap()
+}
+
+fn bytes(b: &[u8]) -> JsValue {
+    Uint8Array::from(b).into()
+}
+
+fn to_vec(val: &JsValue) -> Vec<u8> {
+    val.clone().dyn_into::<Uint8Array>().unwrap().to_vec()
+}
+
+fn obj(fields: &[(&str, JsValue)]) -> JsValue {
+    let val: JsValue = Object::new().into();
+    for (key, field) in fields {
+        Reflect::set(&val, &(*key).into(), field).unwrap();
+    }
+    val
+}
+
+async fn admin_ws() -> AdminWebsocket {
+    reset_stub();
+    connect_admin_ws("ws://localhost:1234".into(), Some(1000))
+        .await
+        .unwrap()
+}
+
+async fn app_ws() -> AppWebsocket {
+    reset_stub();
+    connect_app_ws("ws://localhost:5678".into(), None)
+        .await
+        .unwrap()
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// connect
+////////////////////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen_test]
+async fn connect_passes_url_and_timeout() {
+    let _ = admin_ws().await;
+    let (method, args) = last_call();
+    assert_eq!(method, "connect");
+    assert_eq!(args.get(0).as_string().unwrap(), "ws://localhost:1234");
+    assert_eq!(args.get(1).as_f64(), Some(1000.0));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// AdminWsCmd
+////////////////////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen_test]
+async fn attach_app_interface() {
+    let ws = admin_ws().await;
+    respond("attachAppInterface", &obj(&[("port", 8888.into())]));
+    let resp = ws
+        .call(AdminWsCmd::AttachAppInterface { port: 8888 })
+        .await
+        .unwrap();
+    let (method, args) = last_call();
+    assert_eq!(method, "attachAppInterface");
+    assert_eq!(get(&args.get(0), "port").as_f64(), Some(8888.0));
+    match resp {
+        AdminWsCmdResponse::AttachAppInterface(port) => assert_eq!(port, 8888),
+        other => panic!("unexpected response: {:?}", other),
+    }
+}
+
+#[wasm_bindgen_test]
+async fn disable_app() {
+    let ws = admin_ws().await;
+    let resp = ws
+        .call(AdminWsCmd::DisableApp {
+            installed_app_id: "app".into(),
+        })
+        .await
+        .unwrap();
+    let (method, args) = last_call();
+    assert_eq!(method, "disableApp");
+    assert_eq!(
+        get(&args.get(0), "installed_app_id").as_string().unwrap(),
+        "app"
+    );
+    assert!(matches!(resp, AdminWsCmdResponse::DisableApp(_)));
+}
+
+#[wasm_bindgen_test]
+async fn enable_app() {
+    let ws = admin_ws().await;
+    let resp = ws
+        .call(AdminWsCmd::EnableApp {
+            installed_app_id: "app".into(),
+        })
+        .await
+        .unwrap();
+    let (method, args) = last_call();
+    assert_eq!(method, "enableApp");
+    assert_eq!(
+        get(&args.get(0), "installed_app_id").as_string().unwrap(),
+        "app"
+    );
+    assert!(matches!(resp, AdminWsCmdResponse::EnableApp(_)));
+}
+
+#[wasm_bindgen_test]
+async fn generate_agent_pub_key() {
+    let ws = admin_ws().await;
+    respond("generateAgentPubKey", &bytes(&[1, 2, 3]));
+    let resp = ws.call(AdminWsCmd::GenerateAgentPubKey).await.unwrap();
+    let (method, args) = last_call();
+    assert_eq!(method, "generateAgentPubKey");
+    assert_eq!(args.length(), 0);
+    match resp {
+        AdminWsCmdResponse::GenerateAgentPubKey(agent_pk) => {
+            assert_eq!(agent_pk_to_vec_u8(agent_pk), vec![1, 2, 3])
+        }
+        other => panic!("unexpected response: {:?}", other),
+    }
+}
+
+#[wasm_bindgen_test]
+async fn register_dna() {
+    let ws = admin_ws().await;
+    respond("registerDna", &bytes(&[4, 5, 6]));
+    let resp = ws
+        .call(AdminWsCmd::RegisterDna {
+            path: "./dna.dna".into(),
+            uid: Some("uid".into()),
+            properties: None,
+        })
+        .await
+        .unwrap();
+    let (method, args) = last_call();
+    assert_eq!(method, "registerDna");
+    let payload = args.get(0);
+    assert_eq!(get(&payload, "path").as_string().unwrap(), "./dna.dna");
+    assert_eq!(get(&payload, "uid").as_string().unwrap(), "uid");
+    assert!(get(&payload, "properties").is_null());
+    match resp {
+        AdminWsCmdResponse::RegisterDna(dna_hash) => {
+            assert_eq!(to_vec(&dna_hash.serialize_to_js_obj()), vec![4, 5, 6])
+        }
+        other => panic!("unexpected response: {:?}", other),
+    }
+}
+
+#[wasm_bindgen_test]
+async fn install_app() {
+    let ws = admin_ws().await;
+    let agent_key = AgentPk::deserialize_from_js_obj(bytes(&[1]));
+    let hash = DnaHash::deserialize_from_js_obj(bytes(&[2]));
+    let resp = ws
+        .call(AdminWsCmd::InstallApp {
+            installed_app_id: "app".into(),
+            agent_key,
+            dnas: vec![HashRoleProof {
+                hash,
+                role_id: "role".into(),
+                membrane_proof: None,
+            }],
+        })
+        .await
+        .unwrap();
+    let (method, args) = last_call();
+    assert_eq!(method, "installApp");
+    let payload = args.get(0);
+    assert_eq!(
+        get(&payload, "installed_app_id").as_string().unwrap(),
+        "app"
+    );
+    assert_eq!(to_vec(&get(&payload, "agent_key")), vec![1]);
+    let dna = Array::from(&get(&payload, "dnas")).get(0);
+    assert_eq!(to_vec(&get(&dna, "hash")), vec![2]);
+    assert_eq!(get(&dna, "role_id").as_string().unwrap(), "role");
+    assert!(!Reflect::has(&dna, &"membrane_proof".into()).unwrap());
+    assert!(matches!(resp, AdminWsCmdResponse::InstallApp(_)));
+}
+
+#[wasm_bindgen_test]
+async fn install_app_preserves_dna_order() {
+    let ws = admin_ws().await;
+    let dnas = ["first", "second", "third"]
+        .iter()
+        .enumerate()
+        .map(|(idx, role_id)| HashRoleProof {
+            hash: DnaHash::deserialize_from_js_obj(bytes(&[idx as u8])),
+            role_id: (*role_id).into(),
+            membrane_proof: None,
+        })
+        .collect();
+    ws.call(AdminWsCmd::InstallApp {
+        installed_app_id: "app".into(),
+        agent_key: AgentPk::deserialize_from_js_obj(bytes(&[1])),
+        dnas,
+    })
+    .await
+    .unwrap();
+    let (_, args) = last_call();
+    let role_ids: Vec<String> = Array::from(&get(&args.get(0), "dnas"))
+        .iter()
+        .map(|dna| get(&dna, "role_id").as_string().unwrap())
+        .collect();
+    assert_eq!(role_ids, vec!["first", "second", "third"]);
+}
+
+#[wasm_bindgen_test]
+async fn uninstall_app() {
+    let ws = admin_ws().await;
+    let resp = ws
+        .call(AdminWsCmd::UninstallApp {
+            installed_app_id: "app".into(),
+        })
+        .await
+        .unwrap();
+    let (method, args) = last_call();
+    assert_eq!(method, "uninstallApp");
+    assert_eq!(
+        get(&args.get(0), "installed_app_id").as_string().unwrap(),
+        "app"
+    );
+    assert!(matches!(resp, AdminWsCmdResponse::UninstallApp(_)));
+}
+
+#[wasm_bindgen_test]
+async fn list_dnas() {
+    let ws = admin_ws().await;
+    respond("listDnas", &Array::of1(&bytes(&[7])));
+    let resp = ws.call(AdminWsCmd::ListDnas).await.unwrap();
+    assert_eq!(last_call().0, "listDnas");
+    match resp {
+        AdminWsCmdResponse::ListDnas(val) => {
+            assert_eq!(to_vec(&Array::from(&val).get(0)), vec![7])
+        }
+        other => panic!("unexpected response: {:?}", other),
+    }
+}
+
+#[wasm_bindgen_test]
+async fn list_cell_ids() {
+    let ws = admin_ws().await;
+    let cell_id = Array::of2(&bytes(&[1]), &bytes(&[2]));
+    respond("listCellIds", &Array::of1(&cell_id));
+    let resp = ws.call(AdminWsCmd::ListCellIds).await.unwrap();
+    assert_eq!(last_call().0, "listCellIds");
+    match resp {
+        AdminWsCmdResponse::ListCellIds(cell_ids) => {
+            assert_eq!(cell_ids.len(), 1);
+            let (dna_hash, agent_pk) = cell_ids[0].clone();
+            assert_eq!(to_vec(&dna_hash.serialize_to_js_obj()), vec![1]);
+            assert_eq!(agent_pk_to_vec_u8(agent_pk), vec![2]);
+        }
+        other => panic!("unexpected response: {:?}", other),
+    }
+}
+
+#[wasm_bindgen_test]
+async fn list_active_apps() {
+    let ws = admin_ws().await;
+    respond("listActiveApps", &Array::of2(&"a".into(), &"b".into()));
+    let resp = ws.call(AdminWsCmd::ListActiveApps).await.unwrap();
+    assert_eq!(last_call().0, "listActiveApps");
+    match resp {
+        AdminWsCmdResponse::ListActiveApps(apps) => assert_eq!(apps, vec!["a", "b"]),
+        other => panic!("unexpected response: {:?}", other),
+    }
+}
+
+#[wasm_bindgen_test]
+async fn rejection_is_returned_as_err() {
+    let ws = admin_ws().await;
+    reject("listDnas", &"boom".into());
+    match ws.call(AdminWsCmd::ListDnas).await.unwrap_err() {
+        CallError::Js(val) => assert_eq!(val.as_string().unwrap(), "boom"),
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// record & replay
+////////////////////////////////////////////////////////////////////////////////
+
+/// records a `listDnas` answered with a hash, then a `generateAgentPubKey` rejected with an
+/// `Error`, through the stub.
+async fn recorded_session() -> Recording {
+    let ws = admin_ws().await;
+    respond("listDnas", &Array::of1(&bytes(&[1, 2, 3])));
+    reject("generateAgentPubKey", &js_sys::Error::new("boom").into());
+    let recording = Recording::new();
+    let recorded = recording.admin_ws(&ws);
+    recorded.call(AdminWsCmd::ListDnas).await.unwrap();
+    recorded
+        .call(AdminWsCmd::GenerateAgentPubKey)
+        .await
+        .unwrap_err();
+    recording
+}
+
+#[wasm_bindgen_test]
+async fn recording_captures_calls_and_rejections() {
+    let recording = recorded_session().await;
+    assert_eq!(call_count("listDnas"), 1);
+    assert_eq!(call_count("generateAgentPubKey"), 1);
+    let exchanges = recording.exchanges();
+    assert_eq!(exchanges.len(), 2);
+    assert_eq!(exchanges[0].method, "listDnas");
+    let hashes = Array::from(exchanges[0].result.as_ref().unwrap());
+    assert_eq!(to_vec(&hashes.get(0)), vec![1, 2, 3]);
+    assert_eq!(exchanges[1].method, "generateAgentPubKey");
+    let err: js_sys::Error = exchanges[1].result.clone().unwrap_err().dyn_into().unwrap();
+    assert_eq!(String::from(err.message()), "boom");
+}
+
+#[wasm_bindgen_test]
+async fn recording_round_trips_through_json() {
+    let json = recorded_session().await.to_json();
+    assert!(json.contains("$bytes"));
+    assert!(json.contains("$error"));
+    let exchanges = Recording::from_json(&json).unwrap().exchanges();
+    assert_eq!(exchanges.len(), 2);
+    assert_eq!(exchanges[0].method, "listDnas");
+    let hashes = Array::from(exchanges[0].result.as_ref().unwrap());
+    assert!(hashes.get(0).is_instance_of::<Uint8Array>());
+    assert_eq!(to_vec(&hashes.get(0)), vec![1, 2, 3]);
+    let err: js_sys::Error = exchanges[1].result.clone().unwrap_err().dyn_into().unwrap();
+    assert_eq!(String::from(err.name()), "Error");
+    assert_eq!(String::from(err.message()), "boom");
+}
+
+#[wasm_bindgen_test]
+async fn replay_answers_in_order() {
+    let json = recorded_session().await.to_json();
+    reset_stub();
+    let replay = Replay::from_json(&json).unwrap();
+    let ws = replay.admin_ws();
+    match ws.call(AdminWsCmd::ListDnas).await.unwrap() {
+        AdminWsCmdResponse::ListDnas(hashes) => {
+            assert_eq!(to_vec(&Array::from(&hashes).get(0)), vec![1, 2, 3])
+        }
+        other => panic!("unexpected response: {:?}", other),
+    }
+    match ws.call(AdminWsCmd::GenerateAgentPubKey).await.unwrap_err() {
+        CallError::Js(err) => {
+            let err: js_sys::Error = err.dyn_into().unwrap();
+            assert_eq!(String::from(err.message()), "boom");
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+    assert_eq!(replay.remaining(), 0);
+    assert!(replay.verify().is_ok());
+    assert_eq!(call_count("listDnas"), 0);
+}
+
+#[wasm_bindgen_test]
+async fn replay_rejects_and_reports_a_mismatched_call() {
+    let replay = Replay::new(&recorded_session().await);
+    let ws = replay.admin_ws();
+    assert!(matches!(
+        ws.call(AdminWsCmd::GenerateAgentPubKey).await,
+        Err(CallError::Js(_))
+    ));
+    // the unmatched exchange is still next.
+    assert_eq!(replay.remaining(), 2);
+    ws.call(AdminWsCmd::ListDnas).await.unwrap();
+    let problems = replay.verify().unwrap_err();
+    assert!(problems.contains("unexpected request: generateAgentPubKey"));
+}
+
+#[wasm_bindgen_test]
+async fn replay_verify_reports_unreplayed_exchanges() {
+    let replay = Replay::new(&recorded_session().await);
+    let ws = replay.admin_ws();
+    ws.call(AdminWsCmd::ListDnas).await.unwrap();
+    assert_eq!(replay.remaining(), 1);
+    let problems = replay.verify().unwrap_err();
+    assert!(problems.contains("exchange never requested: generateAgentPubKey"));
+    assert!(!problems.contains("unexpected request"));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// deadlines & cancellation
+////////////////////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen_test]
+async fn stuck_call_times_out_with_connection_default() {
+    let mut ws = admin_ws().await;
+    assert_eq!(ws.call_timeout, Some(1000));
+    never_settle("listDnas");
+    ws.call_timeout = Some(10);
+    assert!(matches!(
+        ws.call(AdminWsCmd::ListDnas).await,
+        Err(CallError::Timeout)
+    ));
+}
+
+#[wasm_bindgen_test]
+async fn per_call_timeout_overrides_default() {
+    let ws = app_ws().await;
+    assert_eq!(ws.call_timeout, None);
+    never_settle("appInfo");
+    let res = ws
+        .call_with_options(
+            AppWsCmd::AppInfo {
+                installed_app_id: "app".into(),
+            },
+            CallOptions::default().timeout(10),
+        )
+        .await;
+    assert!(matches!(res, Err(CallError::Timeout)));
+}
+
+#[wasm_bindgen_test]
+async fn dropped_cancel_handle_cancels_call() {
+    let ws = app_ws().await;
+    never_settle("appInfo");
+    let (handle, cancellation) = CancelHandle::pair();
+    drop(handle);
+    let res = ws
+        .call_with_options(
+            AppWsCmd::AppInfo {
+                installed_app_id: "app".into(),
+            },
+            CallOptions::default().cancellation(cancellation),
+        )
+        .await;
+    assert!(matches!(res, Err(CallError::Cancelled)));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// closing
+////////////////////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen_test]
+async fn close_rejects_pending_and_later_calls() {
+    let ws = admin_ws().await;
+    never_settle("listDnas");
+    let pending = ws.call(AdminWsCmd::ListDnas);
+    let closing = async {
+        ws.clone().close().await.unwrap();
+    };
+    let (res, ()) = futures::join!(pending, closing);
+    assert!(matches!(res, Err(CallError::Closed)));
+    assert!(ws.is_closed());
+    assert_eq!(call_count("close"), 1);
+
+    assert!(matches!(
+        ws.call(AdminWsCmd::ListDnas).await,
+        Err(CallError::Closed)
+    ));
+    // closing again is a no-op.
+    ws.close().await.unwrap();
+    assert_eq!(call_count("close"), 1);
+}
+
+#[wasm_bindgen_test]
+async fn dropping_the_last_clone_closes_the_connection() {
+    let ws = app_ws().await;
+    let clone = ws.clone();
+    drop(ws);
+    assert_eq!(call_count("close"), 0);
+    drop(clone);
+    assert_eq!(call_count("close"), 1);
+}
+
+#[wasm_bindgen_test]
+async fn converting_to_js_value_keeps_the_connection_open() {
+    let ws = app_ws().await;
+    let js_ws: JsValue = ws.into();
+    assert_eq!(call_count("close"), 0);
+    assert!(!js_ws.is_undefined());
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// AppWsCmd
+////////////////////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen_test]
+async fn app_info() {
+    let ws = app_ws().await;
+    let cell = obj(&[
+        ("cell_id", Array::of2(&bytes(&[1]), &bytes(&[2])).into()),
+        ("role_id", "role".into()),
+    ]);
+    respond(
+        "appInfo",
+        &obj(&[
+            ("installed_app_id", "app".into()),
+            ("cell_data", Array::of1(&cell).into()),
+            ("status", obj(&[("running", JsValue::NULL)])),
+        ]),
+    );
+    let resp = ws
+        .call(AppWsCmd::AppInfo {
+            installed_app_id: "app".into(),
+        })
+        .await
+        .unwrap();
+    let (method, args) = last_call();
+    assert_eq!(method, "appInfo");
+    assert_eq!(
+        get(&args.get(0), "installed_app_id").as_string().unwrap(),
+        "app"
+    );
+    match resp {
+        AppWsCmdResponse::AppInfo(info) => {
+            assert_eq!(info.installed_app_id, "app");
+            assert_eq!(info.status, "running");
+            let cell_data = info.cell_data();
+            assert_eq!(cell_data.len(), 1);
+            assert_eq!(cell_data[0].role_id, "role");
+            assert_eq!(agent_pk_to_vec_u8(cell_data[0].cell_id.1.clone()), vec![2]);
+            assert!(matches!(
+                &info.cell_info["role"][..],
+                [CellInfo::Provisioned(ProvisionedCell {
+                    dna_modifiers: None,
+                    ..
+                })]
+            ));
+        }
+        other => panic!("unexpected response: {:?}", other),
+    }
+}
+
+#[wasm_bindgen_test]
+async fn app_info_with_cell_info() {
+    let ws = app_ws().await;
+    let cell_id = |dna_hash: u8| Array::of2(&bytes(&[dna_hash]), &bytes(&[2])).into();
+    let modifiers = || {
+        obj(&[
+            ("network_seed", "seed".into()),
+            ("properties", JsValue::NULL),
+            ("origin_time", 1_000.into()),
+        ])
+    };
+    let provisioned = obj(&[(
+        "provisioned",
+        obj(&[
+            ("cell_id", cell_id(1)),
+            ("dna_modifiers", modifiers()),
+            ("name", "chat".into()),
+        ]),
+    )]);
+    let clone = |dna_hash: u8, index: u32, enabled: bool| {
+        obj(&[(
+            "cloned",
+            obj(&[
+                ("cell_id", cell_id(dna_hash)),
+                ("clone_id", format!("chat.{}", index).into()),
+                ("original_dna_hash", bytes(&[1])),
+                ("dna_modifiers", modifiers()),
+                ("name", "group".into()),
+                ("enabled", enabled.into()),
+            ]),
+        )])
+    };
+    let stem = obj(&[(
+        "stem",
+        obj(&[
+            ("original_dna_hash", bytes(&[9])),
+            ("dna_modifiers", modifiers()),
+            ("name", JsValue::NULL),
+        ]),
+    )]);
+    respond(
+        "appInfo",
+        &obj(&[
+            ("installed_app_id", "app".into()),
+            (
+                "cell_info",
+                obj(&[
+                    (
+                        "chat",
+                        Array::of3(&provisioned, &clone(5, 0, true), &clone(6, 1, false)).into(),
+                    ),
+                    ("later", Array::of1(&stem).into()),
+                ]),
+            ),
+            ("status", obj(&[("running", JsValue::NULL)])),
+        ]),
+    );
+    let info = match ws
+        .call(AppWsCmd::AppInfo {
+            installed_app_id: "app".into(),
+        })
+        .await
+        .unwrap()
+    {
+        AppWsCmdResponse::AppInfo(info) => info,
+        other => panic!("unexpected response: {:?}", other),
+    };
+
+    let chat = &info.cell_info["chat"];
+    assert_eq!(chat.len(), 3);
+    match &chat[0] {
+        CellInfo::Provisioned(cell) => {
+            assert_eq!(cell.cell_id, self::cell(1));
+            assert_eq!(cell.dna_modifiers.as_ref().unwrap().network_seed, "seed");
+            assert_eq!(cell.dna_modifiers.as_ref().unwrap().origin_time, 1_000);
+        }
+        other => panic!("unexpected cell: {:?}", other),
+    }
+    let clones = info.clone_cells("chat");
+    assert_eq!(clones.len(), 2);
+    assert_eq!(clones[1].clone_id, CloneId::new("chat", 1));
+    assert_eq!(clones[1].name, "group");
+    assert!(!clones[1].enabled);
+    match &info.cell_info["later"][0] {
+        CellInfo::Stem(stem) => {
+            assert_eq!(
+                to_vec(&stem.original_dna_hash.clone().serialize_to_js_obj()),
+                vec![9]
+            );
+            assert!(stem.name.is_none());
+        }
+        other => panic!("unexpected cell: {:?}", other),
+    }
+    assert!(info.cell_info["later"][0].cell_id().is_none());
+
+    // disabled clones & stems can't be called, so they're left out of the flat list.
+    let roles: Vec<String> = info
+        .cell_data()
+        .into_iter()
+        .map(|cell| cell.role_id)
+        .collect();
+    assert_eq!(roles, vec!["chat", "chat.0"]);
+    assert_eq!(info.cell_id("chat.0"), Some(self::cell(5)));
+    assert_eq!(info.cell_id("chat.1"), None);
+    assert_eq!(info.provisioned_cells().len(), 1);
+}
+
+#[wasm_bindgen_test]
+async fn call_zome() {
+    let ws = app_ws().await;
+    respond("callZome", &"pong".into());
+    let cell_id = (
+        DnaHash::deserialize_from_js_obj(bytes(&[1])),
+        AgentPk::deserialize_from_js_obj(bytes(&[2])),
+    );
+    let resp = ws
+        .call(AppWsCmd::CallZome {
+            cell_id,
+            zome_name: "zome".into(),
+            fn_name: "ping".into(),
+            payload: "ping".into(),
+            provenance: AgentPk::deserialize_from_js_obj(bytes(&[2])),
+            cap: Some(CapSecret::from([7; 64])),
+        })
+        .await
+        .unwrap();
+    let (method, args) = last_call();
+    assert_eq!(method, "callZome");
+    let payload = args.get(0);
+    let cell_id = Array::from(&get(&payload, "cell_id"));
+    assert_eq!(to_vec(&cell_id.get(0)), vec![1]);
+    assert_eq!(to_vec(&cell_id.get(1)), vec![2]);
+    assert_eq!(get(&payload, "zome_name").as_string().unwrap(), "zome");
+    assert_eq!(get(&payload, "fn_name").as_string().unwrap(), "ping");
+    assert_eq!(get(&payload, "payload").as_string().unwrap(), "ping");
+    assert_eq!(to_vec(&get(&payload, "provenance")), vec![2]);
+    assert_eq!(to_vec(&get(&payload, "cap")), vec![7; 64]);
+    match resp {
+        AppWsCmdResponse::CallZome(val) => assert_eq!(val.as_string().unwrap(), "pong"),
+        other => panic!("unexpected response: {:?}", other),
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// signals
+////////////////////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen_test]
+async fn signals_fan_out_to_every_subscriber() {
+    let ws = app_ws().await;
+    let mut first = ws.signals();
+    let mut second = ws.signals();
+
+    // malformed signals are dropped rather than delivered or panicking.
+    emit_signal(&obj(&[("type", "Signal".into())]));
+    emit_signal(&app_signal(&[1], &[2], "hello".into()));
+
+    for sub in [&mut first, &mut second] {
+        let signal = sub.next().await.unwrap();
+        assert_eq!(to_vec(&signal.cell_id.0.serialize_to_js_obj()), vec![1]);
+        assert_eq!(agent_pk_to_vec_u8(signal.cell_id.1), vec![2]);
+        assert_eq!(signal.payload.as_string().unwrap(), "hello");
+    }
+
+    drop(first);
+    emit_signal(&app_signal(&[1], &[2], "again".into()));
+    let signal = second.next().await.unwrap();
+    assert_eq!(signal.payload.as_string().unwrap(), "again");
+}
+
+#[derive(Debug, Deserialize, PartialEq)]
+struct Counter {
+    count: u32,
+}
+
+#[wasm_bindgen_test]
+async fn typed_signals_filter_by_cell_and_zome() {
+    let ws = app_ws().await;
+    let cell_id = (
+        DnaHash::deserialize_from_js_obj(bytes(&[1])),
+        AgentPk::deserialize_from_js_obj(bytes(&[2])),
+    );
+    let mut counters = ws.subscribe_typed::<Counter>(cell_id, "feed");
+    let mut malformed = ws.malformed_signals();
+
+    emit_signal(&zome_signal(&[9], "feed", obj(&[("count", 1.into())])));
+    emit_signal(&zome_signal(&[1], "other", obj(&[("count", 2.into())])));
+    emit_signal(&app_signal(&[1], &[2], obj(&[("count", 4.into())])));
+    emit_signal(&zome_signal(&[1], "feed", obj(&[("count", "nope".into())])));
+    emit_signal(&zome_signal(&[1], "feed", obj(&[("count", 3.into())])));
+
+    assert_eq!(counters.next().await.unwrap(), Counter { count: 3 });
+    let bad = malformed.next().await.unwrap();
+    assert!(bad.reason.contains("feed"));
+    assert_eq!(get(&bad.raw, "count").as_string().unwrap(), "nope");
+}
+
+#[wasm_bindgen_test]
+async fn signals_may_name_their_zome_in_snake_case() {
+    let ws = app_ws().await;
+    let cell_id = (
+        DnaHash::deserialize_from_js_obj(bytes(&[1])),
+        AgentPk::deserialize_from_js_obj(bytes(&[2])),
+    );
+    let mut counters = ws.subscribe_typed::<Counter>(cell_id, "feed");
+    let mut signals = ws.signals();
+
+    let signal = app_signal(&[1], &[2], obj(&[("count", 5.into())]));
+    Reflect::set(&get(&signal, "data"), &"zome_name".into(), &"feed".into()).unwrap();
+    emit_signal(&signal);
+
+    assert_eq!(
+        signals.next().await.unwrap().zome_name.as_deref(),
+        Some("feed")
+    );
+    assert_eq!(counters.next().await.unwrap(), Counter { count: 5 });
+}
+
+#[wasm_bindgen_test]
+async fn system_signals_arrive_alongside_app_signals() {
+    let ws = app_ws().await;
+    let mut all = ws.all_signals();
+    let mut app_only = ws.signals();
+
+    emit_signal(&obj(&[(
+        "System",
+        obj(&[("SuccessfulCountersigning", bytes(&[7]))]),
+    )]));
+    emit_signal(&app_signal(&[1], &[2], "hello".into()));
+
+    match all.next().await.unwrap() {
+        Signal::System(SystemSignal::SuccessfulCountersigning(entry_hash)) => {
+            assert_eq!(to_vec(&entry_hash.serialize_to_js_obj()), vec![7])
+        }
+        other => panic!("unexpected signal: {:?}", other),
+    }
+    assert!(matches!(all.next().await.unwrap(), Signal::App(_)));
+    // the system signal never reaches app-only subscribers.
+    assert_eq!(
+        app_only.next().await.unwrap().payload.as_string().unwrap(),
+        "hello"
+    );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// reconnection
+////////////////////////////////////////////////////////////////////////////////
+
+fn fast_backoff() -> Backoff {
+    Backoff {
+        initial_delay: 1,
+        max_delay: 5,
+        ..Backoff::default()
+    }
+}
+
+#[wasm_bindgen_test]
+fn backoff_grows_exponentially_up_to_the_cap() {
+    let backoff = Backoff {
+        initial_delay: 100,
+        max_delay: 1000,
+        multiplier: 3,
+        max_attempts: None,
+    };
+    let delays: Vec<u32> = (1..=5).map(|failures| backoff.delay(failures)).collect();
+    assert_eq!(delays, vec![100, 300, 900, 1000, 1000]);
+}
+
+#[wasm_bindgen_test]
+async fn reconnects_after_the_socket_closes() {
+    reset_stub();
+    fail_connects(2);
+    let ws = ReconnectingAppWebsocket::new("ws://localhost:5678".into(), None, fast_backoff());
+    let mut states = ws.state_changes();
+    let mut signals = ws.signals();
+    assert_eq!(ws.state(), ConnectionState::Connecting);
+
+    ws.connected().await.unwrap();
+    assert_eq!(call_count("connect"), 3);
+    assert_eq!(states.next().await, Some(ConnectionState::Open));
+
+    close_socket();
+    assert_eq!(states.next().await, Some(ConnectionState::Reconnecting));
+    assert_eq!(states.next().await, Some(ConnectionState::Open));
+    assert_eq!(call_count("connect"), 4);
+
+    // subscriptions made before the drop are fed by the new connection.
+    emit_signal(&app_signal(&[1], &[2], "after".into()));
+    assert_eq!(
+        signals.next().await.unwrap().payload.as_string().unwrap(),
+        "after"
+    );
+
+    respond("callZome", &"pong".into());
+    let resp = ws
+        .call(AppWsCmd::CallZome {
+            cell_id: (
+                DnaHash::deserialize_from_js_obj(bytes(&[1])),
+                AgentPk::deserialize_from_js_obj(bytes(&[2])),
+            ),
+            zome_name: "zome".into(),
+            fn_name: "ping".into(),
+            payload: JsValue::NULL,
+            provenance: AgentPk::deserialize_from_js_obj(bytes(&[2])),
+            cap: None,
+        })
+        .await
+        .unwrap();
+    assert!(matches!(resp, AppWsCmdResponse::CallZome(val) if val.as_string().unwrap() == "pong"));
+}
+
+#[wasm_bindgen_test]
+async fn gives_up_after_max_attempts() {
+    reset_stub();
+    fail_connects(3);
+    let ws = ReconnectingAdminWebsocket::new(
+        "ws://localhost:1234".into(),
+        None,
+        Backoff {
+            max_attempts: Some(2),
+            ..fast_backoff()
+        },
+    );
+    assert!(matches!(ws.connected().await, Err(CallError::Closed)));
+    assert_eq!(ws.state(), ConnectionState::Closed);
+    assert_eq!(call_count("connect"), 2);
+    assert!(matches!(
+        ws.call(AdminWsCmd::ListDnas).await,
+        Err(CallError::Closed)
+    ));
+}
+
+#[wasm_bindgen_test]
+async fn closing_stops_reconnection() {
+    reset_stub();
+    let ws = ReconnectingAppWebsocket::new("ws://localhost:5678".into(), None, fast_backoff());
+    ws.connected().await.unwrap();
+    let mut states = ws.state_changes();
+
+    ws.close().await.unwrap();
+    assert_eq!(states.next().await, Some(ConnectionState::Closed));
+    assert_eq!(call_count("close"), 1);
+    assert_eq!(call_count("connect"), 1);
+    assert!(matches!(ws.connected().await, Err(CallError::Closed)));
+}
+
+#[wasm_bindgen_test]
+async fn closing_fails_pending_reconnecting_calls() {
+    reset_stub();
+    let ws = ReconnectingAppWebsocket::new("ws://localhost:5678".into(), None, fast_backoff());
+    ws.connected().await.unwrap();
+    never_settle("callZome");
+
+    // the deadline only keeps the test from hanging if the call isn't failed by `close`.
+    let pending = ws.call_with_options(zome_call("stuck"), CallOptions::default().timeout(1000));
+    let closing = async {
+        ws.close().await.unwrap();
+    };
+    let (res, ()) = futures::join!(pending, closing);
+    assert!(matches!(res, Err(CallError::Closed)));
+    assert_eq!(call_count("callZome"), 1);
+    assert!(matches!(
+        ws.call(zome_call("later")).await,
+        Err(CallError::Closed)
+    ));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// offline queue
+////////////////////////////////////////////////////////////////////////////////
+
+fn zome_call(fn_name: &str) -> AppWsCmd {
+    AppWsCmd::CallZome {
+        cell_id: (
+            DnaHash::deserialize_from_js_obj(bytes(&[1])),
+            AgentPk::deserialize_from_js_obj(bytes(&[2])),
+        ),
+        zome_name: "zome".into(),
+        fn_name: fn_name.into(),
+        payload: obj(&[("n", 1.into())]),
+        provenance: AgentPk::deserialize_from_js_obj(bytes(&[2])),
+        cap: Some(CapSecret::from([7; 64])),
+    }
+}
+
+/// the `fn_name`s of every zome call made so far, in order.
+fn zome_calls_made() -> Vec<String> {
+    Array::from(&get(&stub(), "calls"))
+        .iter()
+        .filter(|call| get(call, "method").as_string().unwrap() == "callZome")
+        .map(|call| {
+            let payload = Array::from(&get(&call, "args")).get(0);
+            get(&payload, "fn_name").as_string().unwrap()
+        })
+        .collect()
+}
+
+#[wasm_bindgen_test]
+async fn queued_calls_wait_for_the_connection_and_keep_their_order() {
+    reset_stub();
+    fail_connects(2);
+    respond("callZome", &"pong".into());
+    let storage = MemoryStorage::default();
+    let ws = ReconnectingAppWebsocket::new("ws://localhost:5678".into(), None, fast_backoff());
+    let queue = ZomeCallQueue::new(ws, storage.clone());
+
+    let first = queue.enqueue(zome_call("first"));
+    let second = queue.enqueue(zome_call("second"));
+    assert_eq!(storage.load().len(), 2);
+
+    let (first, second) = futures::join!(first, second);
+    for res in [first, second] {
+        assert!(
+            matches!(res, Ok(AppWsCmdResponse::CallZome(val)) if val.as_string().unwrap() == "pong")
+        );
+    }
+    assert_eq!(zome_calls_made(), vec!["first", "second"]);
+    assert!(storage.load().is_empty());
+    assert!(queue.is_empty());
+}
+
+#[wasm_bindgen_test]
+async fn unstorable_calls_are_rejected_rather_than_queued() {
+    reset_stub();
+    let storage = MemoryStorage::default();
+    let ws = ReconnectingAppWebsocket::new("ws://localhost:5678".into(), None, fast_backoff());
+    let queue = ZomeCallQueue::new(ws, storage.clone());
+    let mut cmd = zome_call("big");
+    if let AppWsCmd::CallZome { payload, .. } = &mut cmd {
+        *payload = obj(&[("n", JsValue::from(1_i64))]);
+    }
+
+    assert!(matches!(queue.enqueue(cmd).await, Err(CallError::Js(_))));
+    assert!(queue.is_empty());
+    assert!(storage.load().is_empty());
+    assert!(zome_calls_made().is_empty());
+}
+
+#[wasm_bindgen_test]
+async fn unsent_calls_are_replayed_from_storage() {
+    reset_stub();
+    fail_connects(1);
+    let storage = MemoryStorage::default();
+    let ws = ReconnectingAppWebsocket::new(
+        "ws://localhost:5678".into(),
+        None,
+        Backoff {
+            max_attempts: Some(1),
+            ..fast_backoff()
+        },
+    );
+    let queue = ZomeCallQueue::new(ws, storage.clone());
+    let call = queue.enqueue(zome_call("later"));
+    assert!(matches!(call.await, Err(CallError::Closed)));
+    assert_eq!(storage.load().len(), 1);
+
+    // e.g. after a page reload.
+    respond("callZome", &"pong".into());
+    let ws = ReconnectingAppWebsocket::new("ws://localhost:5678".into(), None, fast_backoff());
+    let queue = ZomeCallQueue::new(ws, storage.clone());
+    let restored = queue.restored();
+    assert_eq!(restored.len(), 1);
+    for call in restored {
+        assert!(call.await.is_ok());
+    }
+    assert_eq!(zome_calls_made(), vec!["later"]);
+    let (_, args) = last_call();
+    let payload = args.get(0);
+    assert_eq!(get(&get(&payload, "payload"), "n").as_f64(), Some(1.0));
+    assert_eq!(to_vec(&get(&payload, "provenance")), vec![2]);
+    assert!(storage.load().is_empty());
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// retries
+////////////////////////////////////////////////////////////////////////////////
+
+fn fast_retries() -> RetryPolicy {
+    RetryPolicy {
+        backoff: fast_backoff(),
+        ..RetryPolicy::default()
+    }
+}
+
+#[wasm_bindgen_test]
+async fn errors_are_classified_by_conductor_error_type() {
+    let err = CallError::Js(conductor_error("ribosome_error", "internal_error"));
+    assert_eq!(
+        ErrorKind::of(&err),
+        Some(ErrorKind::Conductor("ribosome_error".into()))
+    );
+    assert_eq!(err.conductor_error().unwrap().error_type, "ribosome_error");
+    assert_eq!(
+        ErrorKind::of(&CallError::Js("socket hang up".into())),
+        Some(ErrorKind::Client)
+    );
+    assert_eq!(ErrorKind::of(&CallError::Timeout), Some(ErrorKind::Timeout));
+    assert_eq!(ErrorKind::of(&CallError::Cancelled), None);
+}
+
+#[wasm_bindgen_test]
+async fn transient_failures_are_retried() {
+    let ws = app_ws().await;
+    reject_next(
+        "callZome",
+        &[
+            conductor_error("internal_error", "source chain head has moved"),
+            conductor_error("internal_error", "source chain head has moved"),
+        ],
+    );
+    respond("callZome", &"pong".into());
+    let resp = ws
+        .call_with_retry(zome_call("write"), &fast_retries())
+        .await
+        .unwrap();
+    assert!(matches!(resp, AppWsCmdResponse::CallZome(val) if val.as_string().unwrap() == "pong"));
+    assert_eq!(call_count("callZome"), 3);
+}
+
+#[wasm_bindgen_test]
+async fn other_failures_and_exhausted_attempts_are_returned() {
+    let ws = app_ws().await;
+    reject_next(
+        "callZome",
+        &[conductor_error("ribosome_error", "internal_error")],
+    );
+    let err = ws
+        .call_with_retry(zome_call("write"), &fast_retries())
+        .await
+        .unwrap_err();
+    assert_eq!(err.conductor_error().unwrap().error_type, "ribosome_error");
+    assert_eq!(call_count("callZome"), 1);
+
+    reject("callZome", &conductor_error("internal_error", "still busy"));
+    let err = ws
+        .call_with_retry(zome_call("write"), &fast_retries())
+        .await
+        .unwrap_err();
+    assert_eq!(err.conductor_error().unwrap().error_type, "internal_error");
+    assert_eq!(call_count("callZome"), 4);
+}
+
+#[wasm_bindgen_test]
+async fn guest_errors_and_timeouts_are_not_retried_by_default() {
+    let ws = app_ws().await;
+    reject_next(
+        "callZome",
+        &[conductor_error(
+            "ribosome_error",
+            r#"RuntimeError: WasmError { file: "src/lib.rs", line: 12, error: Guest("no such post") }"#,
+        )],
+    );
+    ws.call_with_retry(zome_call("write"), &fast_retries())
+        .await
+        .unwrap_err();
+    assert_eq!(call_count("callZome"), 1);
+
+    assert!(!fast_retries().is_retryable(&CallError::Timeout));
+    let policy = RetryPolicy {
+        retryable: vec![ErrorKind::Timeout],
+        ..fast_retries()
+    };
+    assert!(policy.is_retryable(&CallError::Timeout));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// zome call errors
+////////////////////////////////////////////////////////////////////////////////
+
+async fn failed_zome_call(error: JsValue) -> ZomeCallError {
+    let ws = app_ws().await;
+    reject("callZome", &error);
+    ws.call(zome_call("write"))
+        .await
+        .map_err(ZomeCallError::from)
+        .unwrap_err()
+}
+
+#[wasm_bindgen_test]
+async fn validation_failures_carry_their_reason() {
+    let err = failed_zome_call(conductor_error(
+        "internal_error",
+        r#"Source chain error: InvalidCommit("title must not be \"empty\"")"#,
+    ))
+    .await;
+    match err {
+        ZomeCallError::ValidationFailed { reason } => {
+            assert_eq!(reason, r#"title must not be "empty""#)
+        }
+        other => panic!("unexpected error: {:?}", other),
+    }
+}
+
+#[wasm_bindgen_test]
+async fn guest_errors_carry_their_message() {
+    let err = failed_zome_call(conductor_error(
+        "ribosome_error",
+        r#"Wasm runtime error while working with Ribosome: RuntimeError: WasmError { file: "src/lib.rs", line: 12, error: Guest("no such post") }"#,
+    ))
+    .await;
+    assert!(matches!(err, ZomeCallError::WasmError { guest } if guest == "no such post"));
+}
+
+#[wasm_bindgen_test]
+async fn other_zome_call_errors() {
+    assert!(matches!(
+        failed_zome_call(conductor_error("zome_call_unauthorized", "no cap grant")).await,
+        ZomeCallError::CapabilityUnauthorized
+    ));
+    assert!(matches!(
+        failed_zome_call(conductor_error("ribosome_error", "zome not found")).await,
+        ZomeCallError::RibosomeError(message) if message == "zome not found"
+    ));
+    assert!(matches!(
+        failed_zome_call(conductor_error("internal_error", "cell missing")).await,
+        ZomeCallError::Internal(message) if message == "cell missing"
+    ));
+    assert!(matches!(
+        failed_zome_call("socket hang up".into()).await,
+        ZomeCallError::Call(CallError::Js(_))
+    ));
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// capabilities
+////////////////////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen_test]
+fn cap_secrets_convert_to_and_from_base64() {
+    let secret = CapSecret::random();
+    assert_ne!(secret, CapSecret::random());
+    assert_eq!(CapSecret::from_base64(&secret.to_base64()).unwrap(), secret);
+    assert!(CapSecret::from_base64("c2hvcnQ=").is_err());
+    assert!(CapSecret::try_from(&[0u8; 63][..]).is_err());
+    assert_eq!(format!("{:?}", secret), "CapSecret(..)");
+}
+
+#[wasm_bindgen_test]
+async fn grant_assigned_capability() {
+    let ws = admin_ws().await;
+    let cell_id = (
+        DnaHash::deserialize_from_js_obj(bytes(&[1])),
+        AgentPk::deserialize_from_js_obj(bytes(&[2])),
+    );
+    let secret = ws
+        .grant_assigned(
+            cell_id,
+            "friends",
+            vec![("posts".into(), "create_post".into())],
+            vec![AgentPk::deserialize_from_js_obj(bytes(&[3]))],
+        )
+        .await
+        .unwrap();
+
+    let (method, args) = last_call();
+    assert_eq!(method, "grantZomeCallCapability");
+    let payload = args.get(0);
+    assert_eq!(
+        to_vec(&Array::from(&get(&payload, "cell_id")).get(0)),
+        vec![1]
+    );
+    let grant = get(&payload, "cap_grant");
+    assert_eq!(get(&grant, "tag").as_string().unwrap(), "friends");
+    let functions = Array::from(&get(&grant, "functions"));
+    let function = Array::from(&functions.get(0));
+    assert_eq!(function.get(0).as_string().unwrap(), "posts");
+    assert_eq!(function.get(1).as_string().unwrap(), "create_post");
+    let access = get(&get(&grant, "access"), "Assigned");
+    assert_eq!(to_vec(&get(&access, "secret")), secret.as_bytes().to_vec());
+    assert_eq!(
+        to_vec(&Array::from(&get(&access, "assignees")).get(0)),
+        vec![3]
+    );
+}
+
+#[wasm_bindgen_test]
+fn unrestricted_access_is_a_bare_tag() {
+    let access = CapAccess::Unrestricted.serialize_to_js_obj();
+    assert_eq!(access.as_string().unwrap(), "Unrestricted");
+    assert!(CapAccess::Unrestricted.secret().is_none());
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// signing
+////////////////////////////////////////////////////////////////////////////////
+
+fn cell(dna_hash: u8) -> CellId {
+    (
+        DnaHash::deserialize_from_js_obj(bytes(&[dna_hash])),
+        AgentPk::deserialize_from_js_obj(bytes(&[2])),
+    )
+}
+
+#[wasm_bindgen_test]
+async fn authorizing_grants_the_signing_key_an_assigned_capability() {
+    let ws = admin_ws().await;
+    let credentials = ws
+        .authorize_signing_credentials(cell(1), vec![("zome".into(), "ping".into())])
+        .await
+        .unwrap();
+
+    let signing_key = agent_pk_to_vec_u8(credentials.signing_key());
+    assert_eq!(signing_key.len(), 39);
+    assert_eq!(signing_key[..3], [0x84, 0x20, 0x24]);
+
+    let (method, args) = last_call();
+    assert_eq!(method, "grantZomeCallCapability");
+    let grant = get(&args.get(0), "cap_grant");
+    assert_eq!(get(&grant, "tag").as_string().unwrap(), SIGNING_GRANT_TAG);
+    let access = get(&get(&grant, "access"), "Assigned");
+    assert_eq!(
+        to_vec(&get(&access, "secret")),
+        credentials.cap_secret().as_bytes().to_vec()
+    );
+    assert_eq!(
+        to_vec(&Array::from(&get(&access, "assignees")).get(0)),
+        signing_key
+    );
+    assert!(!format!("{:?}", credentials).contains("keypair"));
+}
+
+#[wasm_bindgen_test]
+async fn zome_calls_are_signed_with_the_cells_credentials() {
+    let signer = ZomeCallSigner::default();
+    let credentials = SigningCredentials::generate();
+    signer.add(cell(1), credentials.clone());
+    let ws = app_ws().await.sign_zome_calls(signer);
+    respond("callZome", &"pong".into());
+
+    let resp = ws.call(zome_call("ping")).await.unwrap();
+    assert!(matches!(resp, AppWsCmdResponse::CallZome(val) if val.as_string().unwrap() == "pong"));
+
+    let (method, args) = last_call();
+    assert_eq!(method, "callZome");
+    let request = args.get(0);
+    let provenance = to_vec(&get(&request, "provenance"));
+    assert_eq!(provenance, agent_pk_to_vec_u8(credentials.signing_key()));
+    assert_eq!(
+        to_vec(&get(&request, "cap_secret")),
+        credentials.cap_secret().as_bytes().to_vec()
+    );
+    // `{ n: 1 }`, msgpack-encoded.
+    assert_eq!(
+        to_vec(&get(&request, "payload")),
+        vec![0x81, 0xa1, b'n', 0x01]
+    );
+    let nonce: [u8; 32] = to_vec(&get(&request, "nonce")).try_into().unwrap();
+    let expires_at = get(&request, "expires_at").as_f64().unwrap() as i64;
+    assert!(expires_at > (js_sys::Date::now() * 1000.0) as i64);
+
+    let unsigned = ZomeCallUnsigned {
+        provenance: credentials.signing_key(),
+        cell_id: cell(1),
+        zome_name: "zome".into(),
+        fn_name: "ping".into(),
+        cap_secret: Some(credentials.cap_secret().clone()),
+        payload: vec![0x81, 0xa1, b'n', 0x01],
+        nonce,
+        expires_at,
+    };
+    let signature: [u8; 64] = to_vec(&get(&request, "signature")).try_into().unwrap();
+    let public_key: [u8; 32] = provenance[3..35].try_into().unwrap();
+    ed25519_dalek::VerifyingKey::from_bytes(&public_key)
+        .unwrap()
+        .verify_strict(
+            &unsigned.data_to_sign(),
+            &ed25519_dalek::Signature::from_bytes(&signature),
+        )
+        .unwrap();
+}
+
+#[wasm_bindgen_test]
+async fn calls_to_cells_without_credentials_go_out_unsigned() {
+    let signer = ZomeCallSigner::default();
+    signer.add(cell(9), SigningCredentials::generate());
+    let ws = app_ws().await.sign_zome_calls(signer);
+    respond("callZome", &"pong".into());
+
+    ws.call(zome_call("ping")).await.unwrap();
+    let request = last_call().1.get(0);
+    assert!(get(&request, "signature").is_undefined());
+    assert_eq!(to_vec(&get(&request, "cap")), vec![7; 64]);
+}
+
+#[wasm_bindgen_test]
+async fn reconnected_websockets_keep_signing() {
+    reset_stub();
+    let ws = ReconnectingAppWebsocket::new("ws://localhost:5678".into(), None, fast_backoff());
+    let mut states = ws.state_changes();
+    ws.connected().await.unwrap();
+    assert_eq!(states.next().await, Some(ConnectionState::Open));
+    // credentials added after connecting are still used.
+    ws.signer().add(cell(1), SigningCredentials::generate());
+    close_socket();
+    assert_eq!(states.next().await, Some(ConnectionState::Reconnecting));
+    assert_eq!(states.next().await, Some(ConnectionState::Open));
+
+    respond("callZome", &"pong".into());
+    ws.call(zome_call("ping")).await.unwrap();
+    assert!(get(&last_call().1.get(0), "signature").is_object());
+}
+
+#[wasm_bindgen_test]
+fn payloads_encode_like_msgpack_js() {
+    let payload = obj(&[
+        ("a", Array::of3(&1.into(), &(-1).into(), &1.5.into()).into()),
+        ("b", bytes(&[1, 2])),
+        ("c", JsValue::NULL),
+    ]);
+    assert_eq!(
+        encode_payload(&payload).unwrap(),
+        vec![
+            0x83, 0xa1, b'a', 0x93, 0x01, 0xff, 0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0, 0xa1, b'b',
+            0xc4, 0x02, 0x01, 0x02, 0xa1, b'c', 0xc0,
+        ]
+    );
+}
+
+#[wasm_bindgen_test]
+fn sealed_credentials_only_unseal_with_their_key() {
+    let key = SealingKey::generate();
+    let credentials = SigningCredentials::generate();
+    let sealed = key.seal(&credentials);
+    assert_ne!(
+        sealed,
+        key.seal(&credentials),
+        "every seal gets a fresh nonce"
+    );
+
+    let stored = SealedCredentials::from_bytes(sealed.as_bytes().to_vec());
+    let restored = key.unseal(&stored).unwrap();
+    assert_eq!(restored.signing_key(), credentials.signing_key());
+    assert_eq!(restored.cap_secret(), credentials.cap_secret());
+    assert!(SealingKey::generate().unseal(&stored).is_none());
+    assert!(key
+        .unseal(&SealedCredentials::from_bytes(vec![1, 2, 3]))
+        .is_none());
+    assert_eq!(format!("{:?}", key), "SealingKey { .. }");
+}
+
+#[wasm_bindgen_test]
+fn encrypted_key_stores_round_trip_through_a_blob() {
+    let store = EncryptedKeyStore::new("correct horse");
+    let credentials = SigningCredentials::generate();
+    store.set(cell(1), credentials.clone());
+    let blob = store.to_blob();
+    assert_ne!(blob, store.to_blob(), "every blob gets a fresh nonce");
+
+    let loaded = EncryptedKeyStore::from_blob(&blob, "correct horse").unwrap();
+    assert_eq!(loaded.len(), 1);
+    let restored = loaded.get(&cell(1)).unwrap();
+    assert_eq!(restored.signing_key(), credentials.signing_key());
+    assert_eq!(restored.cap_secret(), credentials.cap_secret());
+    assert!(loaded.get(&cell(2)).is_none());
+
+    assert_eq!(
+        EncryptedKeyStore::from_blob(&blob, "battery staple").unwrap_err(),
+        KeyStoreError::WrongPassphrase
+    );
+    assert!(matches!(
+        EncryptedKeyStore::from_blob("AQID", "correct horse"),
+        Err(KeyStoreError::Malformed(_))
+    ));
+}
+
+#[wasm_bindgen_test]
+async fn signers_use_their_key_store() {
+    let store = EncryptedKeyStore::new("correct horse");
+    let signer = ZomeCallSigner::new(store.clone());
+    let credentials = SigningCredentials::generate();
+    signer.add(cell(1), credentials.clone());
+    assert_eq!(store.len(), 1);
+
+    let ws = app_ws().await.sign_zome_calls(signer);
+    respond("callZome", &"pong".into());
+    ws.call(zome_call("ping")).await.unwrap();
+    assert_eq!(
+        to_vec(&get(&last_call().1.get(0), "provenance")),
+        agent_pk_to_vec_u8(credentials.signing_key())
+    );
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// app client
+////////////////////////////////////////////////////////////////////////////////
+
+/// an `appInfo` response for `app`, with a cell per `(role_id, dna hash)`.
+fn app_info_with(roles: &[(&str, u8)]) -> JsValue {
+    let cells: Array = roles
+        .iter()
+        .map(|(role_id, dna_hash)| {
+            obj(&[
+                (
+                    "cell_id",
+                    Array::of2(&bytes(&[*dna_hash]), &bytes(&[2])).into(),
+                ),
+                ("role_id", (*role_id).into()),
+            ])
+        })
+        .collect();
+    obj(&[
+        ("installed_app_id", "app".into()),
+        ("cell_data", cells.into()),
+        ("status", obj(&[("running", JsValue::NULL)])),
+    ])
+}
+
+#[wasm_bindgen_test]
+async fn calls_by_role_fill_in_cell_id_and_provenance() {
+    let client = AppClient::new(app_ws().await, "app");
+    respond("appInfo", &app_info_with(&[("chat", 1), ("profiles", 3)]));
+    respond("callZome", &"pong".into());
+
+    let resp = client
+        .call_zome_by_role("profiles", "zome", "ping", JsValue::NULL)
+        .await
+        .unwrap();
+    assert_eq!(resp.as_string().unwrap(), "pong");
+    let request = last_call().1.get(0);
+    let cell_id = Array::from(&get(&request, "cell_id"));
+    assert_eq!(to_vec(&cell_id.get(0)), vec![3]);
+    assert_eq!(to_vec(&get(&request, "provenance")), vec![2]);
+    assert!(get(&request, "cap").is_null());
+
+    // the app info is cached.
+    client
+        .call_zome_by_role("chat", "zome", "ping", JsValue::NULL)
+        .await
+        .unwrap();
+    assert_eq!(call_count("appInfo"), 1);
+}
+
+#[wasm_bindgen_test]
+async fn unknown_roles_refresh_the_app_info() {
+    let client = AppClient::new(app_ws().await, "app");
+    respond("appInfo", &app_info_with(&[("chat", 1)]));
+    client.app_info().await.unwrap();
+
+    respond("appInfo", &app_info_with(&[("chat", 1), ("channel", 4)]));
+    let (dna_hash, _) = client.cell_id("channel").await.unwrap();
+    assert_eq!(to_vec(&dna_hash.serialize_to_js_obj()), vec![4]);
+    assert_eq!(call_count("appInfo"), 2);
+
+    assert!(matches!(
+        client.cell_id("nope").await,
+        Err(AppClientError::UnknownRole(role_id)) if role_id == "nope"
+    ));
+    assert_eq!(call_count("appInfo"), 3);
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// clone cells
+////////////////////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen_test]
+fn clone_ids_parse_from_role_ids() {
+    assert_eq!(
+        "chat.0".parse::<CloneId>().unwrap(),
+        CloneId::new("chat", 0)
+    );
+    assert_eq!(
+        "my.chat.12".parse::<CloneId>().unwrap(),
+        CloneId::new("my.chat", 12)
+    );
+    assert!("chat".parse::<CloneId>().is_err());
+    assert!("chat.x".parse::<CloneId>().is_err());
+    assert!(".1".parse::<CloneId>().is_err());
+    assert_eq!(CloneId::new("chat", 3).to_string(), "chat.3");
+}
+
+#[wasm_bindgen_test]
+async fn create_clone_cell() {
+    let client = AppClient::new(app_ws().await, "app");
+    respond("appInfo", &app_info_with(&[("chat", 1)]));
+    client.app_info().await.unwrap();
+    // an `InstalledCell`, as older conductors respond.
+    respond(
+        "createCloneCell",
+        &obj(&[
+            ("cell_id", Array::of2(&bytes(&[5]), &bytes(&[2])).into()),
+            ("role_id", "chat.0".into()),
+        ]),
+    );
+    let cell = client
+        .create_clone_cell(
+            "chat",
+            DnaModifiersOpt {
+                network_seed: Some("group-1".into()),
+                ..DnaModifiersOpt::default()
+            },
+            Some("group 1".into()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(cell.clone_id, CloneId::new("chat", 0));
+    assert_eq!(cell.cell_id, self::cell(5));
+
+    let (method, args) = last_call();
+    assert_eq!(method, "createCloneCell");
+    let request = args.get(0);
+    assert_eq!(get(&request, "app_id").as_string().unwrap(), "app");
+    assert_eq!(get(&request, "role_id").as_string().unwrap(), "chat");
+    assert_eq!(get(&request, "name").as_string().unwrap(), "group 1");
+    let modifiers = get(&request, "modifiers");
+    assert_eq!(
+        get(&modifiers, "network_seed").as_string().unwrap(),
+        "group-1"
+    );
+    assert!(get(&modifiers, "properties").is_null());
+
+    // the clone is found by role, alongside the provisioned cell.
+    respond("appInfo", &app_info_with(&[("chat", 1), ("chat.0", 5)]));
+    let clones = client.clone_cells("chat").await.unwrap();
+    assert_eq!(clones, vec![cell]);
+    let app_info = client.app_info().await.unwrap();
+    assert_eq!(app_info.provisioned_cells().len(), 1);
+    assert_eq!(app_info.provisioned_cells()[0].role_id, "chat");
+    assert!(client.cell_id("chat.0").await.is_ok());
+}
+
+#[wasm_bindgen_test]
+async fn disable_and_enable_clone_cells() {
+    let client = AppClient::new(app_ws().await, "app");
+    respond("disableCloneCell", &JsValue::NULL);
+    client
+        .disable_clone_cell(CloneCellId::CloneId(CloneId::new("chat", 0)))
+        .await
+        .unwrap();
+    let (method, args) = last_call();
+    assert_eq!(method, "disableCloneCell");
+    assert_eq!(
+        get(&args.get(0), "clone_cell_id").as_string().unwrap(),
+        "chat.0"
+    );
+
+    respond(
+        "enableCloneCell",
+        &obj(&[
+            ("cell_id", Array::of2(&bytes(&[5]), &bytes(&[2])).into()),
+            ("clone_id", "chat.0".into()),
+        ]),
+    );
+    let cell = client
+        .enable_clone_cell(CloneCellId::CellId(cell(5)))
+        .await
+        .unwrap();
+    assert_eq!(cell.clone_id, CloneId::new("chat", 0));
+    let clone_cell_id = Array::from(&get(&last_call().1.get(0), "clone_cell_id"));
+    assert_eq!(to_vec(&clone_cell_id.get(0)), vec![5]);
+}
+
+#[wasm_bindgen_test]
+async fn delete_archived_clone_cells() {
+    let ws = admin_ws().await;
+    ws.call(AdminWsCmd::DeleteArchivedCloneCells {
+        app_id: "app".into(),
+        role_id: "chat".into(),
+    })
+    .await
+    .unwrap();
+    let (method, args) = last_call();
+    assert_eq!(method, "deleteArchivedCloneCells");
+    assert_eq!(get(&args.get(0), "role_id").as_string().unwrap(), "chat");
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// provisioning
+////////////////////////////////////////////////////////////////////////////////
+
+fn provision_spec() -> AppSpec {
+    let mut spec = AppSpec::new(
+        "app",
+        vec![DnaSpec::new("chat", "./chat.dna"), {
+            let mut dna = DnaSpec::new("files", "./files.dna");
+            dna.membrane_proof = Some(vec![7, 7].into());
+            dna
+        }],
+    );
+    spec.app_port = Some(8888);
+    spec
+}
+
+#[wasm_bindgen_test]
+async fn provision_app_runs_every_step_in_order() {
+    let ws = admin_ws().await;
+    respond("generateAgentPubKey", &bytes(&[1]));
+    respond("registerDna", &bytes(&[2]));
+    respond("attachAppInterface", &obj(&[("port", 8888.into())]));
+    let mut progress = Vec::new();
+    let app = ws
+        .provision_app(provision_spec(), |p| progress.push(p))
+        .await
+        .unwrap();
+
+    let chat = ProvisionStep::RegisterDna {
+        role_id: "chat".into(),
+    };
+    let files = ProvisionStep::RegisterDna {
+        role_id: "files".into(),
+    };
+    let steps = [
+        ProvisionStep::GenerateAgentPubKey,
+        chat,
+        files,
+        ProvisionStep::InstallApp,
+        ProvisionStep::EnableApp,
+        ProvisionStep::AttachAppInterface { port: 8888 },
+    ];
+    let expected: Vec<_> = steps
+        .into_iter()
+        .flat_map(|step| {
+            [
+                ProvisionProgress::Started(step.clone()),
+                ProvisionProgress::Completed(step),
+            ]
+        })
+        .collect();
+    assert_eq!(progress, expected);
+
+    assert_eq!(agent_pk_to_vec_u8(app.agent_key), vec![1]);
+    assert_eq!(app.dnas.len(), 2);
+    assert_eq!(app.app_port, Some(8888));
+    let (method, args) = last_call();
+    assert_eq!(method, "attachAppInterface");
+    assert_eq!(get(&args.get(0), "port").as_f64().unwrap(), 8888.0);
+
+    let calls = Array::from(&get(&stub(), "calls"));
+    let install = calls
+        .iter()
+        .find(|call| get(call, "method").as_string().unwrap() == "installApp")
+        .unwrap();
+    let dnas = Array::from(&get(&Array::from(&get(&install, "args")).get(0), "dnas"));
+    assert_eq!(get(&dnas.get(0), "role_id").as_string().unwrap(), "chat");
+    assert_eq!(get(&dnas.get(1), "role_id").as_string().unwrap(), "files");
+    assert_eq!(to_vec(&get(&dnas.get(1), "membrane_proof")), vec![7, 7]);
+    assert_eq!(call_count("uninstallApp"), 0);
+}
+
+#[wasm_bindgen_test]
+async fn provision_app_skips_steps_it_has_no_need_for() {
+    let ws = admin_ws().await;
+    respond("registerDna", &bytes(&[2]));
+    let mut spec = provision_spec();
+    spec.agent_key = Some(AgentPk::deserialize_from_js_obj(bytes(&[9])));
+    spec.app_port = None;
+    let app = ws.provision_app(spec, |_| {}).await.unwrap();
+    assert_eq!(agent_pk_to_vec_u8(app.agent_key), vec![9]);
+    assert_eq!(call_count("generateAgentPubKey"), 0);
+    assert_eq!(call_count("attachAppInterface"), 0);
+    assert_eq!(last_call().0, "enableApp");
+}
+
+#[wasm_bindgen_test]
+async fn provision_app_uninstalls_after_a_later_step_fails() {
+    let ws = admin_ws().await;
+    respond("generateAgentPubKey", &bytes(&[1]));
+    respond("registerDna", &bytes(&[2]));
+    reject("enableApp", &conductor_error("internal_error", "no"));
+    let mut progress = Vec::new();
+    let err = ws
+        .provision_app(provision_spec(), |p| progress.push(p))
+        .await
+        .unwrap_err();
+    assert_eq!(err.step, ProvisionStep::EnableApp);
+    assert!(err.rollback_error.is_none());
+    assert_eq!(call_count("uninstallApp"), 1);
+    assert_eq!(call_count("attachAppInterface"), 0);
+    assert_eq!(
+        get(&last_call().1.get(0), "installed_app_id")
+            .as_string()
+            .unwrap(),
+        "app"
+    );
+    assert_eq!(
+        progress[progress.len() - 3..],
+        [
+            ProvisionProgress::Failed(ProvisionStep::EnableApp),
+            ProvisionProgress::Started(ProvisionStep::UninstallApp),
+            ProvisionProgress::Completed(ProvisionStep::UninstallApp),
+        ]
+    );
+}
+
+#[wasm_bindgen_test]
+async fn provision_app_reports_a_failed_rollback() {
+    let ws = admin_ws().await;
+    respond("generateAgentPubKey", &bytes(&[1]));
+    respond("registerDna", &bytes(&[2]));
+    reject(
+        "attachAppInterface",
+        &conductor_error("internal_error", "in use"),
+    );
+    reject("uninstallApp", &conductor_error("internal_error", "busy"));
+    let err = ws
+        .provision_app(provision_spec(), |_| {})
+        .await
+        .unwrap_err();
+    assert_eq!(err.step, ProvisionStep::AttachAppInterface { port: 8888 });
+    assert!(err.rollback_error.is_some());
+    assert!(err.to_string().contains("uninstalling the app failed"));
+}
+
+#[wasm_bindgen_test]
+async fn provision_app_does_not_uninstall_before_installing() {
+    let ws = admin_ws().await;
+    respond("generateAgentPubKey", &bytes(&[1]));
+    reject_next("registerDna", &[conductor_error("internal_error", "no")]);
+    respond("registerDna", &bytes(&[2]));
+    let err = ws
+        .provision_app(provision_spec(), |_| {})
+        .await
+        .unwrap_err();
+    assert_eq!(
+        err.step,
+        ProvisionStep::RegisterDna {
+            role_id: "chat".into()
+        }
+    );
+    assert_eq!(call_count("registerDna"), 1);
+    assert_eq!(call_count("installApp"), 0);
+    assert_eq!(call_count("uninstallApp"), 0);
+}
+
+#[wasm_bindgen_test]
+async fn list_apps() {
+    let ws = admin_ws().await;
+    respond("listApps", &Array::of1(&app_info_with(&[("chat", 1)])));
+    let resp = ws
+        .call(AdminWsCmd::ListApps {
+            status_filter: Some(AppStatusFilter::Running),
+        })
+        .await
+        .unwrap();
+    let (method, args) = last_call();
+    assert_eq!(method, "listApps");
+    assert_eq!(
+        get(&args.get(0), "status_filter").as_string().unwrap(),
+        "running"
+    );
+    match resp {
+        AdminWsCmdResponse::ListApps(apps) => {
+            assert_eq!(apps.len(), 1);
+            assert_eq!(apps[0].cell_id("chat"), Some(cell(1)));
+        }
+        other => panic!("unexpected response: {:?}", other),
+    }
+}
+
+#[wasm_bindgen_test]
+async fn ensure_app_installed_leaves_an_installed_app_alone() {
+    let ws = admin_ws().await;
+    respond("listApps", &Array::of1(&app_info_with(&[("chat", 1)])));
+    let change = ws.ensure_app_installed(provision_spec()).await.unwrap();
+    assert_eq!(change, AppChange::Unchanged);
+    assert_eq!(last_call().0, "listApps");
+    assert_eq!(call_count("installApp"), 0);
+}
+
+#[wasm_bindgen_test]
+async fn ensure_app_installed_installs_a_missing_app() {
+    let ws = admin_ws().await;
+    respond("listApps", &Array::new());
+    respond("generateAgentPubKey", &bytes(&[1]));
+    respond("registerDna", &bytes(&[2]));
+    let change = ws.ensure_app_installed(provision_spec()).await.unwrap();
+    match change {
+        AppChange::Installed(app) => {
+            assert_eq!(app.installed_app_id, "app");
+            assert_eq!(app.dnas.len(), 2);
+            assert_eq!(app.app_port, None);
+        }
+        other => panic!("unexpected change: {:?}", other),
+    }
+    assert_eq!(last_call().0, "installApp");
+    assert_eq!(call_count("enableApp"), 0);
+}
+
+#[wasm_bindgen_test]
+async fn ensure_app_installed_reports_failing_to_list_apps() {
+    let ws = admin_ws().await;
+    reject("listApps", &conductor_error("internal_error", "boom"));
+    let err = ws.ensure_app_installed(provision_spec()).await.unwrap_err();
+    assert_eq!(err.step, ProvisionStep::ListApps);
+    assert_eq!(err.step.to_string(), "listing the installed apps");
+    assert_eq!(call_count("installApp"), 0);
+}
+
+#[wasm_bindgen_test]
+async fn ensure_app_enabled_leaves_a_running_app_alone() {
+    let ws = admin_ws().await;
+    respond("listActiveApps", &Array::of1(&"app".into()));
+    respond("listApps", &Array::of1(&app_info_with(&[("chat", 1)])));
+    respond("listCellIds", &Array::of1(&cell(1).serialize_to_js_obj()));
+    let change = ws.ensure_app_enabled("app").await.unwrap();
+    assert_eq!(change, AppChange::Unchanged);
+    assert_eq!(call_count("enableApp"), 0);
+}
+
+#[wasm_bindgen_test]
+async fn ensure_app_enabled_enables_an_inactive_app() {
+    let ws = admin_ws().await;
+    respond("listActiveApps", &Array::new());
+    let change = ws.ensure_app_enabled("app").await.unwrap();
+    assert_eq!(change, AppChange::Enabled);
+    let (method, args) = last_call();
+    assert_eq!(method, "enableApp");
+    assert_eq!(
+        get(&args.get(0), "installed_app_id").as_string().unwrap(),
+        "app"
+    );
+}
+
+#[wasm_bindgen_test]
+async fn ensure_app_enabled_restarts_an_app_whose_cells_are_stopped() {
+    let ws = admin_ws().await;
+    respond("listActiveApps", &Array::of1(&"app".into()));
+    respond(
+        "listApps",
+        &Array::of1(&app_info_with(&[("chat", 1), ("files", 3)])),
+    );
+    respond("listCellIds", &Array::of1(&cell(1).serialize_to_js_obj()));
+    let change = ws.ensure_app_enabled("app").await.unwrap();
+    assert_eq!(change, AppChange::Enabled);
+    assert_eq!(call_count("enableApp"), 1);
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// InstallAppBuilder
+////////////////////////////////////////////////////////////////////////////////
+
+fn agent_key(b: u8) -> AgentPk {
+    AgentPk::deserialize_from_js_obj(bytes(&[b]))
+}
+
+fn dna_hash(b: u8) -> DnaHash {
+    DnaHash::deserialize_from_js_obj(bytes(&[b]))
+}
+
+#[wasm_bindgen_test]
+fn install_app_builder_builds_the_command() {
+    let cmd = InstallAppBuilder::new("app", agent_key(1))
+        .role(InstallRole::hash("chat", dna_hash(2)).membrane_proof(vec![7, 8]))
+        .role(InstallRole::hash("files", dna_hash(3)))
+        .build()
+        .unwrap();
+    match cmd {
+        AdminWsCmd::InstallApp {
+            installed_app_id,
+            agent_key: key,
+            dnas,
+        } => {
+            assert_eq!(installed_app_id, "app");
+            assert_eq!(key, agent_key(1));
+            assert_eq!(
+                dnas,
+                vec![
+                    HashRoleProof {
+                        hash: dna_hash(2),
+                        role_id: "chat".into(),
+                        membrane_proof: Some(vec![7, 8].into()),
+                    },
+                    HashRoleProof {
+                        hash: dna_hash(3),
+                        role_id: "files".into(),
+                        membrane_proof: None,
+                    },
+                ]
+            );
+        }
+        other => panic!("unexpected command: {:?}", other),
+    }
+}
+
+#[wasm_bindgen_test]
+fn install_app_builder_rejects_invalid_apps() {
+    let builder = || InstallAppBuilder::new("app", agent_key(1));
+    assert!(matches!(
+        InstallAppBuilder::new("", agent_key(1))
+            .role(InstallRole::hash("chat", dna_hash(2)))
+            .build(),
+        Err(InstallAppError::EmptyAppId)
+    ));
+    assert!(matches!(builder().build(), Err(InstallAppError::NoRoles)));
+    assert!(matches!(
+        builder()
+            .role(InstallRole::hash("chat", dna_hash(2)))
+            .role(InstallRole::path("chat", "./chat.dna"))
+            .build(),
+        Err(InstallAppError::DuplicateRole(role_id)) if role_id == "chat"
+    ));
+    assert!(matches!(
+        builder()
+            .role(InstallRole::hash("chat", dna_hash(2)).network_seed("seed"))
+            .build(),
+        Err(InstallAppError::ModifiersWithHash(role_id)) if role_id == "chat"
+    ));
+    assert!(matches!(
+        builder()
+            .role(InstallRole::path("chat", "./chat.dna"))
+            .build(),
+        Err(InstallAppError::UnregisteredDna(role_id)) if role_id == "chat"
+    ));
+}
+
+#[wasm_bindgen_test]
+async fn install_app_registers_dnas_given_by_path() {
+    let ws = admin_ws().await;
+    respond("listApps", &Array::new());
+    respond("registerDna", &bytes(&[4]));
+    let builder = InstallAppBuilder::new("app", agent_key(1))
+        .role(InstallRole::hash("chat", dna_hash(2)))
+        .role(
+            InstallRole::path("files", "./files.dna")
+                .network_seed("seed")
+                .membrane_proof(vec![9]),
+        );
+    ws.install_app(builder).await.unwrap();
+
+    let calls = Array::from(&get(&stub(), "calls"));
+    let register = Array::from(&get(&calls.at(-2), "args")).get(0);
+    assert_eq!(get(&register, "path").as_string().unwrap(), "./files.dna");
+    assert_eq!(get(&register, "uid").as_string().unwrap(), "seed");
+
+    let (method, args) = last_call();
+    assert_eq!(method, "installApp");
+    let dnas = Array::from(&get(&args.get(0), "dnas"));
+    assert_eq!(to_vec(&get(&dnas.get(0), "hash")), vec![2]);
+    assert_eq!(to_vec(&get(&dnas.get(1), "hash")), vec![4]);
+    assert_eq!(to_vec(&get(&dnas.get(1), "membrane_proof")), vec![9]);
+}
+
+#[wasm_bindgen_test]
+async fn install_app_refuses_an_installed_app_id() {
+    let ws = admin_ws().await;
+    respond("listApps", &Array::of1(&app_info_with(&[("chat", 1)])));
+    let builder =
+        InstallAppBuilder::new("app", agent_key(1)).role(InstallRole::path("chat", "./chat.dna"));
+    let err = ws.install_app(builder).await.unwrap_err();
+    assert!(matches!(err, InstallAppError::AppIdTaken(id) if id == "app"));
+    assert_eq!(call_count("registerDna"), 0);
+    assert_eq!(call_count("installApp"), 0);
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// membrane proofs
+////////////////////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen_test]
+fn membrane_proofs_from_bytes_and_payloads() {
+    let proof = MembraneProof::from(Uint8Array::from(&[1, 2][..]));
+    assert_eq!(proof.as_bytes(), [1, 2]);
+    assert_eq!(to_vec(&proof.serialize_to_js_obj()), vec![1, 2]);
+
+    let proof = MembraneProof::encode(obj(&[("code", "x".into())])).unwrap();
+    assert_eq!(
+        proof.as_bytes(),
+        [0x81, 0xa4, b'c', b'o', b'd', b'e', 0xa1, b'x']
+    );
+}
+
+#[wasm_bindgen_test]
+fn invite_codes_admit_their_invitee() {
+    let inviter = InviterKey::from_seed(&[3; 32]);
+    let invitee = agent_key(8);
+    let invite = inviter.invite(&dna_hash(2), &invitee);
+    assert!(invite.verify());
+    assert_eq!(invite.inviter(), inviter.public_key());
+
+    let parsed: InviteCode = invite.to_string().parse().unwrap();
+    assert_eq!(parsed, invite);
+    let trusted = inviter.public_key();
+    assert!(parsed.admits(&dna_hash(2), &invitee, &trusted));
+    assert!(!parsed.admits(&dna_hash(3), &invitee, &trusted));
+    assert!(!parsed.admits(&dna_hash(2), &agent_key(9), &trusted));
+
+    let proof = MembraneProof::from(parsed);
+    assert_eq!(
+        InviteCode::from_bytes(proof.as_bytes()).unwrap().invitee(),
+        invitee
+    );
+}
+
+#[wasm_bindgen_test]
+fn invite_codes_signed_by_someone_else_are_rejected() {
+    let invite = InviterKey::from_seed(&[3; 32]).invite(&dna_hash(2), &agent_key(8));
+    let forged = InviterKey::from_seed(&[4; 32]).invite(&dna_hash(2), &agent_key(8));
+    // a self-signed invite verifies, but isn't from the trusted inviter.
+    assert!(forged.verify());
+    let trusted = InviterKey::from_seed(&[3; 32]).public_key();
+    assert!(!forged.admits(&dna_hash(2), &agent_key(8), &trusted));
+    let mut bytes = invite.to_bytes();
+    let mut forged_bytes = forged.to_bytes();
+    // swap in the other inviter's key, keeping the signature.
+    let key_at = |bytes: &[u8]| {
+        bytes
+            .windows(3)
+            .position(|w| w == [0x84, 0x20, 0x24])
+            .unwrap()
+    };
+    let (at, forged_at) = (key_at(&bytes), key_at(&forged_bytes));
+    bytes[at..at + 39].swap_with_slice(&mut forged_bytes[forged_at..forged_at + 39]);
+    assert!(!InviteCode::from_bytes(&bytes).unwrap().verify());
+    assert!("not an invite".parse::<InviteCode>().is_err());
+}
+
+#[wasm_bindgen_test]
+async fn installing_with_an_invite_code() {
+    let ws = admin_ws().await;
+    respond("listApps", &Array::new());
+    let invite = InviterKey::from_seed(&[3; 32]).invite(&dna_hash(2), &agent_key(1));
+    let builder = InstallAppBuilder::new("app", agent_key(1))
+        .role(InstallRole::hash("chat", dna_hash(2)).membrane_proof(invite.clone()));
+    ws.install_app(builder).await.unwrap();
+    let dna = Array::from(&get(&last_call().1.get(0), "dnas")).get(0);
+    assert_eq!(to_vec(&get(&dna, "membrane_proof")), invite.to_bytes());
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// app interfaces
+////////////////////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen_test]
+async fn list_app_interfaces() {
+    let ws = admin_ws().await;
+    respond("listAppInterfaces", &Array::of2(&8888.into(), &9999.into()));
+    assert_eq!(ws.app_interfaces().await.unwrap(), vec![8888, 9999]);
+    assert_eq!(last_call().0, "listAppInterfaces");
+
+    // newer conductors describe each interface.
+    respond(
+        "listAppInterfaces",
+        &Array::of1(&obj(&[
+            ("port", 7777.into()),
+            ("installed_app_id", JsValue::NULL),
+        ])),
+    );
+    assert_eq!(ws.app_interfaces().await.unwrap(), vec![7777]);
+}
+
+#[wasm_bindgen_test]
+async fn attaching_on_port_0_returns_the_conductors_choice() {
+    let ws = admin_ws().await;
+    respond("attachAppInterface", &obj(&[("port", 45678.into())]));
+    assert_eq!(ws.attach_app_interface(0).await.unwrap(), 45678);
+    assert_eq!(get(&last_call().1.get(0), "port").as_f64(), Some(0.0));
+}
+
+/// the url of the most recent app websocket connection.
+fn last_app_ws_url() -> String {
+    Array::from(&get(&stub(), "calls"))
+        .iter()
+        .rfind(|call| {
+            get(call, "method").as_string().unwrap() == "connect"
+                && get(call, "kind").as_string().unwrap() == "app"
+        })
+        .map(|call| Array::from(&get(&call, "args")).get(0).as_string().unwrap())
+        .unwrap()
+}
+
+#[wasm_bindgen_test]
+async fn app_ws_reuses_an_existing_interface() {
+    let ws = admin_ws().await;
+    respond("listAppInterfaces", &Array::of1(&8888.into()));
+    ws.app_ws().await.unwrap();
+    assert_eq!(call_count("attachAppInterface"), 0);
+    assert_eq!(last_app_ws_url(), "ws://localhost:8888");
+}
+
+#[wasm_bindgen_test]
+async fn app_ws_attaches_an_interface_if_there_are_none() {
+    let ws = admin_ws().await;
+    respond("listAppInterfaces", &Array::new());
+    respond("attachAppInterface", &obj(&[("port", 45678.into())]));
+    ws.app_ws().await.unwrap();
+    assert_eq!(call_count("attachAppInterface"), 1);
+    assert_eq!(last_app_ws_url(), "ws://localhost:45678");
+}
+
+#[wasm_bindgen_test]
+async fn app_interface_urls_keep_the_admin_host() {
+    let mut ws = admin_ws().await;
+    ws.url = Some("wss://conductor.example:4444/admin".into());
+    assert_eq!(ws.app_interface_url(80), "wss://conductor.example:80");
+    ws.url = Some("ws://[::1]:4444".into());
+    assert_eq!(ws.app_interface_url(80), "ws://[::1]:80");
+    ws.url = Some("ws://[::1]".into());
+    assert_eq!(ws.app_interface_url(80), "ws://[::1]:80");
+    ws.url = None;
+    assert_eq!(ws.app_interface_url(80), "ws://localhost:80");
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// launcher environment
+////////////////////////////////////////////////////////////////////////////////
+
+/// sets (or with `None`, removes) a global, as the launcher or the page's url would.
+fn set_global(key: &str, val: Option<JsValue>) {
+    match val {
+        Some(val) => Reflect::set(&js_sys::global(), &key.into(), &val).unwrap(),
+        None => Reflect::delete_property(&js_sys::global(), &key.into()).unwrap(),
+    };
+}
+
+fn launcher_env(app_port: u16, installed_app_id: &str) -> JsValue {
+    obj(&[
+        ("APP_INTERFACE_PORT", app_port.into()),
+        ("INSTALLED_APP_ID", installed_app_id.into()),
+    ])
+}
+
+#[wasm_bindgen_test]
+fn launcher_env_from_js_and_query() {
+    let mut env = LauncherEnv::new(8888, "app");
+    assert_eq!(
+        LauncherEnv::from_js(&launcher_env(8888, "app")),
+        Some(env.clone())
+    );
+    assert_eq!(
+        LauncherEnv::from_js(&obj(&[("INSTALLED_APP_ID", "app".into())])),
+        None
+    );
+    assert_eq!(LauncherEnv::from_js(&JsValue::UNDEFINED), None);
+
+    env.admin_port = Some(4444);
+    assert_eq!(
+        LauncherEnv::from_query("?app_port=8888&installed_app_id=app&admin_port=4444"),
+        Some(env)
+    );
+    assert_eq!(
+        LauncherEnv::from_query("installed_app_id=my%20app&app_port=1")
+            .unwrap()
+            .installed_app_id,
+        "my app"
+    );
+    assert_eq!(
+        LauncherEnv::from_query("?app_port=nope&installed_app_id=app"),
+        None
+    );
+    assert_eq!(LauncherEnv::from_query(""), None);
+}
+
+#[wasm_bindgen_test]
+fn launcher_env_prefers_the_injected_env() {
+    set_global(
+        "location",
+        Some(obj(&[("search", "?app_port=1&installed_app_id=q".into())])),
+    );
+    set_global("__HC_LAUNCHER_ENV__", Some(launcher_env(8888, "app")));
+    assert_eq!(LauncherEnv::detect(), Some(LauncherEnv::new(8888, "app")));
+
+    set_global("__HC_LAUNCHER_ENV__", None);
+    assert_eq!(LauncherEnv::detect(), Some(LauncherEnv::new(1, "q")));
+
+    set_global("location", None);
+    assert_eq!(LauncherEnv::detect(), None);
+    assert_eq!(
+        LauncherEnv::detect_or(LauncherEnv::new(2, "config")),
+        LauncherEnv::new(2, "config")
+    );
+}
+
+#[wasm_bindgen_test]
+async fn connect_from_env_binds_the_client_to_the_app() {
+    reset_stub();
+    set_global("__HC_LAUNCHER_ENV__", Some(launcher_env(8888, "app")));
+    let client = connect_from_env(None, Some(500)).await.unwrap();
+    set_global("__HC_LAUNCHER_ENV__", None);
+    assert_eq!(client.installed_app_id(), "app");
+    assert_eq!(last_app_ws_url(), "ws://localhost:8888");
+
+    let client = connect_from_env(Some(LauncherEnv::new(9999, "config")), None)
+        .await
+        .unwrap();
+    assert_eq!(client.installed_app_id(), "config");
+    assert_eq!(last_app_ws_url(), "ws://localhost:9999");
+
+    assert!(matches!(
+        connect_from_env(None, None).await,
+        Err(LauncherEnvError::NotDetected)
+    ));
+}
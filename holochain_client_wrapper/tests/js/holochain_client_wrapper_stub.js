@@ -0,0 +1,102 @@
+// stand-in for the esbuild bundle of `holochain-client-js`, used when building with the
+// `js-stub` feature.
+//
+// every client method resolves with whatever the test queued under its name in
+// `globalThis.__hcStub.responses` (rejects with `errors[name]`, or never settles if `name` is in
+// `pending`), and every connect & method call is logged to `globalThis.__hcStub.calls`. an array
+// under `rejections[name]` is consumed first, rejecting one call per entry. the signal callback
+// passed to `AppWebsocket.connect` is kept as `signalCb`, so tests can emit signals through it.
+// each client exposes a fake `client.socket`, appended to `sockets`, whose `close()` fires its
+// `close` listeners like a dropped connection would; `client.close()` is logged as a `close`
+// call and closes that socket. while `connectFailures` is positive, connects reject and
+// decrement it. state lives on `globalThis` because the test binary and the library each get
+// their own copy of this module.
+
+function stubState() {
+  if (!globalThis.__hcStub) {
+    globalThis.__hcStub = {
+      calls: [],
+      responses: {},
+      errors: {},
+      pending: [],
+      rejections: {},
+      sockets: [],
+      connectFailures: 0,
+    };
+  }
+  return globalThis.__hcStub;
+}
+
+function fakeSocket() {
+  const listeners = [];
+  const socket = {
+    readyState: 1,
+    addEventListener(event, listener) {
+      if (event === "close") {
+        listeners.push(listener);
+      }
+    },
+    close() {
+      socket.readyState = 3;
+      listeners.forEach((listener) => listener());
+    },
+  };
+  stubState().sockets.push(socket);
+  return socket;
+}
+
+function fakeClient(kind, url, timeout) {
+  const state = stubState();
+  state.calls.push({ kind, method: "connect", args: [url, timeout] });
+  if (state.connectFailures > 0) {
+    state.connectFailures -= 1;
+    throw new Error("connection refused");
+  }
+  const client = {
+    socket: fakeSocket(),
+    async close() {
+      stubState().calls.push({ kind, method: "close", args: [] });
+      client.socket.close();
+    },
+  };
+  return new Proxy(
+    {},
+    {
+      get(_target, method) {
+        if (typeof method !== "string" || method === "then") {
+          return undefined;
+        }
+        if (method === "client") {
+          return client;
+        }
+        return (...args) => {
+          const state = stubState();
+          state.calls.push({ kind, method, args });
+          if (state.pending.includes(method)) {
+            return new Promise(() => {});
+          }
+          if (state.rejections[method]?.length) {
+            return Promise.reject(state.rejections[method].shift());
+          }
+          if (method in state.errors) {
+            return Promise.reject(state.errors[method]);
+          }
+          return Promise.resolve(state.responses[method]);
+        };
+      },
+    }
+  );
+}
+
+export class AdminWebsocket {
+  static async connect(url, timeout) {
+    return fakeClient("admin", url, timeout);
+  }
+}
+
+export class AppWebsocket {
+  static async connect(url, timeout, signalCb) {
+    stubState().signalCb = signalCb;
+    return fakeClient("app", url, timeout);
+  }
+}
@@ -0,0 +1,61 @@
+//! end-to-end: the real `holochain-client-js` bundle, connected to `mock_conductor`'s
+//! `e2e_server` example over a websocket, rather than to the JS stub.
+//!
+//! start `cargo run --manifest-path ../mock_conductor/Cargo.toml --example e2e_server` first,
+//! then run with `wasm-pack test --node --test mock_conductor` (without `js-stub`). set
+//! `MOCK_CONDUCTOR_URL` if the server isn't on its default port. see the README.
+
+#![cfg(all(target_arch = "wasm32", not(feature = "js-stub")))]
+
+use holochain_client_wrapper::*;
+use js_sys::Reflect;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const DEFAULT_URL: &str = "ws://127.0.0.1:45678";
+
+/// `process.env.MOCK_CONDUCTOR_URL`, or the server's default.
+fn url() -> String {
+    let env = Reflect::get(&js_sys::global(), &"process".into())
+        .and_then(|process| Reflect::get(&process, &"env".into()))
+        .and_then(|env| Reflect::get(&env, &"MOCK_CONDUCTOR_URL".into()));
+    env.ok()
+        .and_then(|url| url.as_string())
+        .unwrap_or_else(|| DEFAULT_URL.into())
+}
+
+/// one test, since the server's script is consumed in order.
+#[wasm_bindgen_test]
+async fn admin_calls_against_the_mock_conductor() {
+    let ws = connect_admin_ws(url(), Some(1000)).await.unwrap();
+
+    match ws.call(AdminWsCmd::ListActiveApps).await.unwrap() {
+        AdminWsCmdResponse::ListActiveApps(apps) => assert_eq!(apps, vec!["app"]),
+        other => panic!("unexpected response: {:?}", other),
+    }
+
+    // never answered: the connection's deadline applies.
+    assert!(matches!(
+        ws.call(AdminWsCmd::ListDnas).await,
+        Err(CallError::Timeout)
+    ));
+
+    // answered, but only after the call's own, shorter deadline.
+    let res = ws
+        .call_with_options(
+            AdminWsCmd::GenerateAgentPubKey,
+            CallOptions::default().timeout(500),
+        )
+        .await;
+    assert!(matches!(res, Err(CallError::Timeout)));
+
+    match ws.call(AdminWsCmd::ListCellIds).await {
+        Err(err @ CallError::Js(_)) => {
+            let conductor_error = err.conductor_error().unwrap();
+            assert_eq!(conductor_error.error_type, "internal_error");
+            assert_eq!(conductor_error.data.as_string().unwrap(), "boom");
+        }
+        other => panic!("unexpected result: {:?}", other),
+    }
+
+    ws.close().await.unwrap();
+}
@@ -0,0 +1,264 @@
+//! property-style checks of the `SerializeToJsObj`/`DeserializeFromJsObj` round-trip contract,
+//! over randomly generated primitives, containers & domain structs.
+//!
+//! values are generated with a small seeded xorshift generator rather than `proptest`, whose RNG
+//! stack doesn't build for `wasm32-unknown-unknown` without extra `getrandom` configuration.
+//!
+//! run with `wasm-pack test --node --features js-stub`.
+
+#![cfg(target_arch = "wasm32")]
+
+use std::fmt::Debug;
+
+use holochain_client_wrapper::*;
+use js_sys::{Array, Uint8Array};
+use wasm_bindgen::JsValue;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+const CASES: u32 = 256;
+const SEED: u64 = 0x5eed_cafe_f00d_d00d;
+
+////////////////////////////////////////////////////////////////////////////////
+// generators
+////////////////////////////////////////////////////////////////////////////////
+
+struct Gen(u64);
+
+impl Gen {
+    fn next_u64(&mut self) -> u64 {
+        // xorshift64*
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next_u64() % n
+    }
+
+    fn bool(&mut self) -> bool {
+        self.below(2) == 0
+    }
+
+    fn u16(&mut self) -> u16 {
+        self.next_u64() as u16
+    }
+
+    /// restricted to the range a JS `Number` represents exactly.
+    fn i64(&mut self) -> i64 {
+        const MAX_SAFE: i64 = (1 << 53) - 1;
+        (self.next_u64() as i64) % MAX_SAFE
+    }
+
+    fn string(&mut self) -> String {
+        const ALPHABET: &[char] = &['a', 'Z', '0', ' ', '_', '.', '"', '\\', 'é', '日', '🦀'];
+        let len = self.below(12);
+        (0..len)
+            .map(|_| ALPHABET[self.below(ALPHABET.len() as u64) as usize])
+            .collect()
+    }
+
+    fn bytes(&mut self) -> JsValue {
+        let len = self.below(40);
+        let bytes: Vec<u8> = (0..len).map(|_| self.next_u64() as u8).collect();
+        Uint8Array::from(bytes.as_slice()).into()
+    }
+
+    fn option<T>(&mut self, f: impl FnOnce(&mut Self) -> T) -> Option<T> {
+        if self.bool() {
+            Some(f(self))
+        } else {
+            None
+        }
+    }
+
+    fn vec<T>(&mut self, mut f: impl FnMut(&mut Self) -> T) -> Vec<T> {
+        let len = self.below(8);
+        (0..len).map(|_| f(self)).collect()
+    }
+
+    /// any non-nullish JS value; `null`/`undefined` would collapse under `Option`.
+    fn js_value(&mut self) -> JsValue {
+        match self.below(4) {
+            0 => self.string().into(),
+            1 => JsValue::from_f64(self.i64() as f64),
+            2 => JsValue::from_bool(self.bool()),
+            _ => self.bytes(),
+        }
+    }
+
+    fn dna_hash(&mut self) -> DnaHash {
+        DnaHash::deserialize_from_js_obj(self.bytes())
+    }
+
+    fn agent_pk(&mut self) -> AgentPk {
+        AgentPk::deserialize_from_js_obj(self.bytes())
+    }
+
+    fn cap_secret(&mut self) -> CapSecret {
+        let mut bytes = [0; CapSecret::LEN];
+        bytes.iter_mut().for_each(|b| *b = self.next_u64() as u8);
+        CapSecret::from(bytes)
+    }
+
+    fn membrane_proof(&mut self) -> MembraneProof {
+        MembraneProof::from(Uint8Array::from(self.bytes()).to_vec())
+    }
+
+    fn cell_id(&mut self) -> CellId {
+        (self.dna_hash(), self.agent_pk())
+    }
+
+    fn hash_role_proof(&mut self) -> HashRoleProof {
+        HashRoleProof {
+            hash: self.dna_hash(),
+            role_id: self.string(),
+            membrane_proof: self.option(Self::membrane_proof),
+        }
+    }
+
+    fn cell_id_role_id(&mut self) -> CellIdRoleId {
+        CellIdRoleId {
+            cell_id: self.cell_id(),
+            role_id: self.string(),
+        }
+    }
+
+    fn clone_id(&mut self) -> CloneId {
+        // any role id will do, so long as it's non-empty.
+        CloneId::new(format!("r{}", self.string()), self.next_u64() as u32)
+    }
+
+    fn dna_modifiers(&mut self) -> DnaModifiers {
+        DnaModifiers {
+            network_seed: self.string(),
+            properties: self.js_value(),
+            origin_time: self.i64(),
+        }
+    }
+
+    fn cloned_cell(&mut self) -> ClonedCell {
+        ClonedCell {
+            cell_id: self.cell_id(),
+            clone_id: self.clone_id(),
+            original_dna_hash: self.option(Self::dna_hash),
+            dna_modifiers: self.option(Self::dna_modifiers),
+            name: self.string(),
+            enabled: self.bool(),
+        }
+    }
+
+    fn cell_info(&mut self) -> CellInfo {
+        match self.below(3) {
+            0 => CellInfo::Provisioned(ProvisionedCell {
+                cell_id: self.cell_id(),
+                dna_modifiers: self.option(Self::dna_modifiers),
+                name: self.string(),
+            }),
+            1 => CellInfo::Cloned(self.cloned_cell()),
+            _ => CellInfo::Stem(StemCell {
+                original_dna_hash: self.dna_hash(),
+                dna_modifiers: self.dna_modifiers(),
+                name: self.option(Self::string),
+            }),
+        }
+    }
+
+    fn app_info(&mut self) -> AppInfo {
+        const STATUSES: &[&str] = &["running", "stopped", "paused"];
+        AppInfo {
+            installed_app_id: self.string(),
+            cell_info: self
+                .vec(|g| (g.string(), g.vec(Self::cell_info)))
+                .into_iter()
+                .collect(),
+            status: STATUSES[self.below(STATUSES.len() as u64) as usize].into(),
+        }
+    }
+}
+
+fn check_round_trip<T>(name: &str, mut gen: impl FnMut(&mut Gen) -> T)
+where
+    T: SerializeToJsObj + DeserializeFromJsObj + Clone + Debug + PartialEq,
+{
+    let mut g = Gen(SEED);
+    for case in 0..CASES {
+        let val = gen(&mut g);
+        let round_tripped = T::deserialize_from_js_obj(val.clone().serialize_to_js_obj());
+        assert_eq!(
+            val, round_tripped,
+            "{}: round-trip failed on case {} (seed {:#x})",
+            name, case, SEED
+        );
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// primitives & containers
+////////////////////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen_test]
+fn primitives_round_trip() {
+    check_round_trip("u16", Gen::u16);
+    check_round_trip("i64", Gen::i64);
+    check_round_trip("String", Gen::string);
+    check_round_trip("JsValue", Gen::js_value);
+}
+
+#[wasm_bindgen_test]
+fn options_round_trip() {
+    check_round_trip("Option<String>", |g| g.option(Gen::string));
+    check_round_trip("Option<Vec<u16>>", |g| g.option(|g| g.vec(Gen::u16)));
+}
+
+#[wasm_bindgen_test]
+fn tuples_round_trip() {
+    check_round_trip("(String, i64)", |g| (g.string(), g.i64()));
+    check_round_trip("(u16, String, Option<i64>)", |g| {
+        (g.u16(), g.string(), g.option(Gen::i64))
+    });
+}
+
+#[wasm_bindgen_test]
+fn vecs_round_trip() {
+    check_round_trip("Vec<String>", |g| g.vec(Gen::string));
+    check_round_trip("Vec<Option<String>>", |g| g.vec(|g| g.option(Gen::string)));
+    check_round_trip("Vec<Vec<u16>>", |g| g.vec(|g| g.vec(Gen::u16)));
+}
+
+#[wasm_bindgen_test]
+fn vec_serialization_preserves_order() {
+    let arr = Array::from(&vec![1u16, 2, 3].serialize_to_js_obj());
+    let elems: Vec<f64> = arr.iter().map(|v| v.as_f64().unwrap()).collect();
+    assert_eq!(elems, vec![1.0, 2.0, 3.0]);
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// domain types
+////////////////////////////////////////////////////////////////////////////////
+
+#[wasm_bindgen_test]
+fn hashes_round_trip() {
+    check_round_trip("DnaHash", Gen::dna_hash);
+    check_round_trip("AgentPk", Gen::agent_pk);
+    check_round_trip("EntryHashRaw", |g| {
+        EntryHashRaw::deserialize_from_js_obj(g.bytes())
+    });
+    check_round_trip("HeaderHashRaw", |g| {
+        HeaderHashRaw::deserialize_from_js_obj(g.bytes())
+    });
+    check_round_trip("CellIdVec", |g| g.vec(Gen::cell_id));
+    check_round_trip("Option<CapSecret>", |g| g.option(Gen::cap_secret));
+}
+
+#[wasm_bindgen_test]
+fn structs_round_trip() {
+    check_round_trip("HashRoleProof", Gen::hash_role_proof);
+    check_round_trip("Vec<HashRoleProof>", |g| g.vec(Gen::hash_role_proof));
+    check_round_trip("CellIdRoleId", Gen::cell_id_role_id);
+    check_round_trip("AppInfo", Gen::app_info);
+    check_round_trip("CloneId", Gen::clone_id);
+    check_round_trip("ClonedCell", Gen::cloned_cell);
+    check_round_trip("CellInfo", Gen::cell_info);
+}
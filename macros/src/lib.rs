@@ -23,9 +23,11 @@ pub fn generate_call(attrs: TokenStream, item: TokenStream) -> TokenStream {
     let enum_name = item_enum.ident.clone();
 
     let mut match_blocks = TokenStream2::new();
+    let mut method_names: Punctuated<String, Comma> = Punctuated::new();
     for variant in &item_enum.variants {
         let variant_name = variant.ident.clone();
         let variant_name_camel_case = lowercase_first_letter(variant.ident.to_string());
+        method_names.push(variant_name_camel_case.clone());
 
         let (method_call_tokenstream, enum_match_binder): (TokenStream2, TokenStream2) =
             match &variant.fields {
@@ -97,8 +99,29 @@ pub fn generate_call(attrs: TokenStream, item: TokenStream) -> TokenStream {
     (quote::quote! {
         #item_enum
 
+        impl #ident_ws_cmd {
+            /// the camelCase names of the JS client methods which this enum dispatches to.
+            pub const METHOD_NAMES: &'static [&'static str] = &[#method_names];
+        }
+
         impl #ident_ws {
-            pub async fn call(&self, cmd: #ident_ws_cmd) -> Result<#ident_ws_cmd_resp, JsValue> {
+            /// makes the call with the connection's default deadline & no cancellation.
+            pub async fn call(&self, cmd: #ident_ws_cmd) -> Result<#ident_ws_cmd_resp, CallError> {
+                self.call_with_options(cmd, CallOptions::default()).await
+            }
+
+            pub async fn call_with_options(
+                &self,
+                cmd: #ident_ws_cmd,
+                opts: CallOptions,
+            ) -> Result<#ident_ws_cmd_resp, CallError> {
+                let timeout = opts.timeout.or(self.call_timeout);
+                self.connection
+                    .guard(with_deadline(self.call_js(cmd), timeout, opts.cancellation))
+                    .await
+            }
+
+            async fn call_js(&self, cmd: #ident_ws_cmd) -> Result<#ident_ws_cmd_resp, JsValue> {
                 match cmd {
                     #match_blocks
                 }
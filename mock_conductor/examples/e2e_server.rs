@@ -0,0 +1,63 @@
+//! serves the script `holochain_client_wrapper/tests/mock_conductor.rs` runs against, so the
+//! wrapper can be tested end-to-end: through `holochain-client-js`, over a real websocket.
+//!
+//! listens on `MOCK_CONDUCTOR_PORT` (default 45678) until every expectation has been consumed, or
+//! `MOCK_CONDUCTOR_TIMEOUT` seconds (default 120) have passed, then verifies the script was
+//! followed, exiting non-zero if not. see the README for the full recipe.
+
+use std::time::Duration;
+
+use mock_conductor::{Expectation, MockConductor, Value};
+
+/// the port `tests/mock_conductor.rs` connects to unless told otherwise.
+const DEFAULT_PORT: u16 = 45678;
+const DEFAULT_TIMEOUT_SECS: u64 = 120;
+/// how long to keep serving once the script is consumed, so the last responses go out and any
+/// stray requests are caught by `verify`.
+const GRACE: Duration = Duration::from_secs(1);
+
+fn env_var<T: std::str::FromStr>(name: &str, default: T) -> T {
+    match std::env::var(name) {
+        Ok(val) => val
+            .parse()
+            .unwrap_or_else(|_| panic!("{} to be a number, not {:?}", name, val)),
+        Err(_) => default,
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let port = env_var("MOCK_CONDUCTOR_PORT", DEFAULT_PORT);
+    let timeout = Duration::from_secs(env_var("MOCK_CONDUCTOR_TIMEOUT", DEFAULT_TIMEOUT_SECS));
+    let conductor = MockConductor::start_on(port)
+        .await
+        .expect("mock conductor to start");
+
+    // in the order the wasm test makes its calls.
+    conductor.expect(
+        Expectation::request("list_active_apps")
+            .respond("active_apps_listed", Value::Array(vec![Value::from("app")])),
+    );
+    conductor.expect(Expectation::request("list_dnas").no_response());
+    conductor.expect(
+        Expectation::request("generate_agent_pub_key")
+            .respond("agent_pub_key_generated", Value::Binary(vec![1; 39]))
+            .delay(Duration::from_secs(2)),
+    );
+    conductor.expect(Expectation::request("list_cell_ids").respond_error("internal_error", "boom"));
+
+    println!("mock conductor listening on {}", conductor.url());
+    let consumed = async {
+        while conductor.remaining() > 0 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    };
+    if tokio::time::timeout(timeout, consumed).await.is_ok() {
+        tokio::time::sleep(GRACE).await;
+    }
+
+    if let Err(problems) = conductor.verify() {
+        eprintln!("{}", problems);
+        std::process::exit(1);
+    }
+}
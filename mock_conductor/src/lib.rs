@@ -0,0 +1,407 @@
+//! a scriptable stand-in for a Holochain conductor, for end-to-end testing of
+//! `holochain_client_wrapper` without installing Holochain.
+//!
+//! the server listens on localhost and speaks the same websocket wire protocol that
+//! `holochain-client-js` uses for both the admin & app interfaces: msgpack-encoded
+//! `WireMessage`s whose `data` is itself a msgpack-encoded `{ type, data }` request/response.
+//!
+//! tests register `Expectation`s (request type + optional payload → canned reply) and can push
+//! signals to every connected client. requests which match no expectation are answered with a
+//! conductor error & recorded, so `verify` can fail the test afterwards.
+
+use std::{
+    collections::VecDeque,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::{broadcast, mpsc},
+    task::JoinHandle,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+pub use rmpv::Value;
+
+////////////////////////////////////////////////////////////////////////////////
+// wire protocol
+////////////////////////////////////////////////////////////////////////////////
+
+/// the outer envelope of every websocket frame, as defined by `holochain_websocket`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum WireMessage {
+    Request {
+        id: u64,
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+    Response {
+        id: u64,
+        data: Option<serde_bytes::ByteBuf>,
+    },
+    Signal {
+        #[serde(with = "serde_bytes")]
+        data: Vec<u8>,
+    },
+}
+
+fn encode<T: Serialize>(val: &T) -> Vec<u8> {
+    rmp_serde::to_vec_named(val).expect("msgpack encoding to succeed")
+}
+
+fn type_data(request_type: &str, data: Value) -> Value {
+    Value::Map(vec![
+        (Value::from("type"), Value::from(request_type)),
+        (Value::from("data"), data),
+    ])
+}
+
+fn map_get<'a>(val: &'a Value, key: &str) -> Option<&'a Value> {
+    val.as_map()?
+        .iter()
+        .find(|(k, _)| k.as_str() == Some(key))
+        .map(|(_, v)| v)
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// script
+////////////////////////////////////////////////////////////////////////////////
+
+/// a request as received from a client, with its `{ type, data }` envelope taken apart.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Request {
+    /// snake_case request type, e.g. `attach_app_interface` or `zome_call`.
+    pub request_type: String,
+    pub data: Value,
+}
+
+#[derive(Clone, Debug)]
+enum Reply {
+    Response { response_type: String, data: Value },
+    Error { error_type: String, message: String },
+    Silent,
+}
+
+/// one scripted request → reply pair.
+///
+/// an expectation is consumed by the first request which matches it. expectations are tried in
+/// the order they were registered.
+#[derive(Clone, Debug)]
+pub struct Expectation {
+    request_type: String,
+    data: Option<Value>,
+    reply: Reply,
+    delay: Option<Duration>,
+}
+
+impl Expectation {
+    /// expect a request of the given snake_case type. replies with `null` data until
+    /// `respond`/`respond_error`/`no_response` is called.
+    pub fn request(request_type: impl Into<String>) -> Self {
+        Expectation {
+            request_type: request_type.into(),
+            data: None,
+            reply: Reply::Response {
+                response_type: String::new(),
+                data: Value::Nil,
+            },
+            delay: None,
+        }
+    }
+
+    /// only match requests whose payload is exactly `data`.
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = Some(data);
+        self
+    }
+
+    /// answer with a successful response of the given snake_case type, e.g.
+    /// `app_interface_attached`.
+    pub fn respond(mut self, response_type: impl Into<String>, data: Value) -> Self {
+        self.reply = Reply::Response {
+            response_type: response_type.into(),
+            data,
+        };
+        self
+    }
+
+    /// answer with a conductor error, e.g. `("ribosome_error", "...")`.
+    pub fn respond_error(
+        mut self,
+        error_type: impl Into<String>,
+        message: impl Into<String>,
+    ) -> Self {
+        self.reply = Reply::Error {
+            error_type: error_type.into(),
+            message: message.into(),
+        };
+        self
+    }
+
+    /// never answer, so the client's timeout handling can be exercised.
+    pub fn no_response(mut self) -> Self {
+        self.reply = Reply::Silent;
+        self
+    }
+
+    /// wait before answering.
+    pub fn delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    fn matches(&self, req: &Request) -> bool {
+        self.request_type == req.request_type
+            && self.data.as_ref().is_none_or(|data| *data == req.data)
+    }
+
+    fn response_type(&self) -> String {
+        match &self.reply {
+            Reply::Response { response_type, .. } if response_type.is_empty() => {
+                self.request_type.clone()
+            }
+            Reply::Response { response_type, .. } => response_type.clone(),
+            _ => String::new(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct State {
+    expectations: VecDeque<Expectation>,
+    received: Vec<Request>,
+    unexpected: Vec<Request>,
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// MockConductor
+////////////////////////////////////////////////////////////////////////////////
+
+/// a running mock conductor. the server shuts down when this is dropped.
+pub struct MockConductor {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    signals: broadcast::Sender<Vec<u8>>,
+    accept_loop: JoinHandle<()>,
+}
+
+impl MockConductor {
+    /// listen on an OS-assigned localhost port.
+    pub async fn start() -> std::io::Result<Self> {
+        Self::start_on(0).await
+    }
+
+    /// listen on the given localhost port.
+    pub async fn start_on(port: u16) -> std::io::Result<Self> {
+        let listener = TcpListener::bind(("127.0.0.1", port)).await?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(State::default()));
+        let (signals, _) = broadcast::channel(64);
+
+        let accept_loop = {
+            let state = state.clone();
+            let signals = signals.clone();
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(serve_connection(stream, state.clone(), signals.subscribe()));
+                }
+            })
+        };
+
+        Ok(MockConductor {
+            addr,
+            state,
+            signals,
+            accept_loop,
+        })
+    }
+
+    pub fn port(&self) -> u16 {
+        self.addr.port()
+    }
+
+    /// the url to hand to `connect_admin_ws`/`connect_app_ws`.
+    pub fn url(&self) -> String {
+        format!("ws://{}", self.addr)
+    }
+
+    pub fn expect(&self, expectation: Expectation) {
+        self.state
+            .lock()
+            .expect("state lock to succeed")
+            .expectations
+            .push_back(expectation);
+    }
+
+    /// push an app signal, as emitted by `emit_signal` in a zome, to every connected client.
+    pub fn emit_app_signal(&self, dna_hash: &[u8], agent_pub_key: &[u8], payload: &Value) {
+        let cell_id = Value::Array(vec![
+            Value::Binary(dna_hash.to_vec()),
+            Value::Binary(agent_pub_key.to_vec()),
+        ]);
+        let signal = Value::Map(vec![(
+            Value::from("App"),
+            Value::Array(vec![cell_id, Value::Binary(encode(payload))]),
+        )]);
+        self.emit_signal(&signal);
+    }
+
+    /// push an arbitrary, already-shaped signal (e.g. `{ System: ... }`) to every connected
+    /// client.
+    pub fn emit_signal(&self, signal: &Value) {
+        let frame = encode(&WireMessage::Signal {
+            data: encode(signal),
+        });
+        // no receivers just means no client is connected yet.
+        let _ = self.signals.send(frame);
+    }
+
+    /// every request received so far, in arrival order.
+    pub fn received(&self) -> Vec<Request> {
+        self.state
+            .lock()
+            .expect("state lock to succeed")
+            .received
+            .clone()
+    }
+
+    /// the number of expectations not yet consumed.
+    pub fn remaining(&self) -> usize {
+        self.state
+            .lock()
+            .expect("state lock to succeed")
+            .expectations
+            .len()
+    }
+
+    /// checks that every expectation was consumed and that no unexpected request arrived.
+    pub fn verify(&self) -> Result<(), String> {
+        let state = self.state.lock().expect("state lock to succeed");
+        let mut problems = Vec::new();
+        for req in &state.unexpected {
+            problems.push(format!("unexpected request: {:?}", req));
+        }
+        for exp in &state.expectations {
+            problems.push(format!("unmet expectation: {}", exp.request_type));
+        }
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems.join("\n"))
+        }
+    }
+}
+
+impl Drop for MockConductor {
+    fn drop(&mut self) {
+        self.accept_loop.abort();
+    }
+}
+
+////////////////////////////////////////////////////////////////////////////////
+// connection handling
+////////////////////////////////////////////////////////////////////////////////
+
+async fn serve_connection(
+    stream: TcpStream,
+    state: Arc<Mutex<State>>,
+    mut signals: broadcast::Receiver<Vec<u8>>,
+) {
+    let ws = match tokio_tungstenite::accept_async(stream).await {
+        Ok(ws) => ws,
+        Err(_) => return,
+    };
+    let (mut sink, mut source) = ws.split();
+    let (out_tx, mut out_rx) = mpsc::unbounded_channel::<Vec<u8>>();
+
+    loop {
+        let frame = tokio::select! {
+            incoming = source.next() => match incoming {
+                Some(Ok(Message::Binary(bytes))) => {
+                    handle_frame(&bytes, &state, &out_tx);
+                    continue;
+                }
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => continue,
+            },
+            Some(frame) = out_rx.recv() => frame,
+            signal = signals.recv() => match signal {
+                Ok(frame) => frame,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+        };
+        if sink.send(Message::Binary(frame.into())).await.is_err() {
+            break;
+        }
+    }
+    let _ = sink.close().await;
+}
+
+fn handle_frame(bytes: &[u8], state: &Mutex<State>, out_tx: &mpsc::UnboundedSender<Vec<u8>>) {
+    let (id, data) = match rmp_serde::from_slice(bytes) {
+        Ok(WireMessage::Request { id, data }) => (id, data),
+        // clients never send responses or signals; ignore anything else.
+        _ => return,
+    };
+    let envelope: Value = match rmp_serde::from_slice(&data) {
+        Ok(val) => val,
+        Err(_) => return,
+    };
+    let req = Request {
+        request_type: map_get(&envelope, "type")
+            .and_then(Value::as_str)
+            .unwrap_or_default()
+            .to_string(),
+        data: map_get(&envelope, "data").cloned().unwrap_or(Value::Nil),
+    };
+
+    let expectation = {
+        let mut state = state.lock().expect("state lock to succeed");
+        state.received.push(req.clone());
+        let found = state.expectations.iter().position(|exp| exp.matches(&req));
+        match found.and_then(|idx| state.expectations.remove(idx)) {
+            Some(exp) => exp,
+            None => {
+                let message = format!("mock conductor: unexpected request: {:?}", req);
+                state.unexpected.push(req);
+                Expectation::request("").respond_error("internal_error", message)
+            }
+        }
+    };
+
+    let response_type = expectation.response_type();
+    let payload = match expectation.reply {
+        Reply::Silent => return,
+        Reply::Response { data, .. } => type_data(&response_type, data),
+        Reply::Error {
+            error_type,
+            message,
+        } => type_data(
+            "error",
+            type_data(&error_type, Value::from(message.as_str())),
+        ),
+    };
+    let frame = encode(&WireMessage::Response {
+        id,
+        data: Some(serde_bytes::ByteBuf::from(encode(&payload))),
+    });
+
+    match expectation.delay {
+        None => {
+            let _ = out_tx.send(frame);
+        }
+        Some(delay) => {
+            let out_tx = out_tx.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                let _ = out_tx.send(frame);
+            });
+        }
+    }
+}
@@ -0,0 +1,127 @@
+use std::time::Duration;
+
+use futures::{SinkExt, StreamExt};
+use mock_conductor::{Expectation, MockConductor, Value};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+/// encodes a request the way `holochain-client-js`'s `WsClient.request` does.
+fn request_frame(id: u64, request_type: &str, data: Value) -> Message {
+    let inner = Value::Map(vec![
+        (Value::from("type"), Value::from(request_type)),
+        (Value::from("data"), data),
+    ]);
+    let outer = Value::Map(vec![
+        (Value::from("id"), Value::from(id)),
+        (Value::from("type"), Value::from("Request")),
+        (Value::from("data"), Value::Binary(to_bytes(&inner))),
+    ]);
+    Message::Binary(to_bytes(&outer).into())
+}
+
+fn to_bytes(val: &Value) -> Vec<u8> {
+    let mut buf = Vec::new();
+    rmpv::encode::write_value(&mut buf, val).unwrap();
+    buf
+}
+
+fn from_bytes(bytes: &[u8]) -> Value {
+    rmpv::decode::read_value(&mut &bytes[..]).unwrap()
+}
+
+fn get<'a>(val: &'a Value, key: &str) -> &'a Value {
+    val.as_map()
+        .unwrap()
+        .iter()
+        .find(|(k, _)| k.as_str() == Some(key))
+        .map(|(_, v)| v)
+        .unwrap()
+}
+
+async fn next_frame<S>(source: &mut S) -> Value
+where
+    S: StreamExt<Item = Result<Message, tokio_tungstenite::tungstenite::Error>> + Unpin,
+{
+    match source.next().await.unwrap().unwrap() {
+        Message::Binary(bytes) => from_bytes(&bytes),
+        other => panic!("unexpected frame: {:?}", other),
+    }
+}
+
+#[tokio::test]
+async fn scripted_response_error_and_signal() {
+    let conductor = MockConductor::start().await.unwrap();
+    let port_data = Value::Map(vec![(Value::from("port"), Value::from(0))]);
+    conductor.expect(
+        Expectation::request("attach_app_interface")
+            .with_data(port_data.clone())
+            .respond(
+                "app_interface_attached",
+                Value::Map(vec![(Value::from("port"), Value::from(8888))]),
+            ),
+    );
+    conductor.expect(Expectation::request("list_dnas").respond_error("internal_error", "boom"));
+
+    let (ws, _) = connect_async(conductor.url()).await.unwrap();
+    let (mut sink, mut source) = ws.split();
+
+    sink.send(request_frame(0, "attach_app_interface", port_data))
+        .await
+        .unwrap();
+    let resp = next_frame(&mut source).await;
+    assert_eq!(get(&resp, "type").as_str(), Some("Response"));
+    assert_eq!(get(&resp, "id").as_u64(), Some(0));
+    let inner = from_bytes(get(&resp, "data").as_slice().unwrap());
+    assert_eq!(get(&inner, "type").as_str(), Some("app_interface_attached"));
+    assert_eq!(get(get(&inner, "data"), "port").as_u64(), Some(8888));
+
+    sink.send(request_frame(1, "list_dnas", Value::Nil))
+        .await
+        .unwrap();
+    let resp = next_frame(&mut source).await;
+    let inner = from_bytes(get(&resp, "data").as_slice().unwrap());
+    assert_eq!(get(&inner, "type").as_str(), Some("error"));
+    assert_eq!(
+        get(get(&inner, "data"), "type").as_str(),
+        Some("internal_error")
+    );
+
+    conductor.emit_app_signal(&[1; 39], &[2; 39], &Value::from("hello"));
+    let signal = next_frame(&mut source).await;
+    assert_eq!(get(&signal, "type").as_str(), Some("Signal"));
+    let inner = from_bytes(get(&signal, "data").as_slice().unwrap());
+    let app = get(&inner, "App").as_array().unwrap();
+    assert_eq!(from_bytes(app[1].as_slice().unwrap()), Value::from("hello"));
+
+    assert_eq!(conductor.received().len(), 2);
+    conductor.verify().unwrap();
+}
+
+#[tokio::test]
+async fn unexpected_and_unanswered_requests_fail_verification() {
+    let conductor = MockConductor::start().await.unwrap();
+    conductor.expect(Expectation::request("list_cell_ids").no_response());
+    conductor.expect(Expectation::request("list_active_apps"));
+
+    let (ws, _) = connect_async(conductor.url()).await.unwrap();
+    let (mut sink, mut source) = ws.split();
+
+    sink.send(request_frame(0, "list_cell_ids", Value::Nil))
+        .await
+        .unwrap();
+    sink.send(request_frame(1, "uninstall_app", Value::Nil))
+        .await
+        .unwrap();
+    let resp = next_frame(&mut source).await;
+    assert_eq!(get(&resp, "id").as_u64(), Some(1));
+    assert!(
+        tokio::time::timeout(Duration::from_millis(50), source.next())
+            .await
+            .is_err(),
+        "silent expectation should never be answered"
+    );
+
+    assert_eq!(conductor.remaining(), 1);
+    let err = conductor.verify().unwrap_err();
+    assert!(err.contains("uninstall_app"));
+    assert!(err.contains("list_active_apps"));
+}